@@ -1,17 +1,161 @@
 extern crate todo_swamp;
 
+use std::env;
 use std::io;
 use std::io::prelude::*;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::process;
 
 use todo_swamp::*;
 
+const USAGE: &str =
+    "usage: application [--stream] [--strict] [--highlight] [--today <YYYY-MM-DD>] \
+                     [--allow-open-subtasks] [--journal <path> [--compact]]";
+
+struct Options {
+    journal: Option<PathBuf>,
+    compact: bool,
+    strict: bool,
+    stream: bool,
+    highlight: bool,
+    today: Option<Date>,
+    allow_open_subtasks: bool,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut opts = Options {
+        journal: None,
+        compact: false,
+        strict: false,
+        stream: false,
+        highlight: false,
+        today: None,
+        allow_open_subtasks: false,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--journal" => match args.next() {
+                Some(path) => opts.journal = Some(PathBuf::from(path)),
+                None => return Err(String::from("--journal expects a path")),
+            },
+            "--compact" => opts.compact = true,
+            "--strict" => opts.strict = true,
+            "--stream" => opts.stream = true,
+            "--highlight" => opts.highlight = true,
+            "--allow-open-subtasks" => opts.allow_open_subtasks = true,
+            "--today" => match args.next().map(|d| d.parse::<Date>()) {
+                Some(Ok(d)) => opts.today = Some(d),
+                Some(Err(e)) => return Err(format!("--today: {}", e)),
+                None => return Err(String::from("--today expects a date")),
+            },
+            _ => return Err(format!("unknown argument {:?}", arg)),
+        }
+    }
+    if opts.compact && opts.journal.is_none() {
+        return Err(String::from("--compact needs a --journal"));
+    }
+    Ok(opts)
+}
+
 pub fn main() {
-    let mut tl: TodoList = TodoList::new();
+    let opts = parse_args().unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        process::exit(2);
+    });
+
+    let mut ws = Workspace::new();
+    if let Some(today) = opts.today {
+        ws.set_clock(Clock::Fixed(today.into()));
+    }
+    ws.set_subtasks_block_done(!opts.allow_open_subtasks);
+    let mut journal = opts.journal.map(|path| {
+        let journal = Journal::open(&path, &mut ws).unwrap_or_else(|e| {
+            eprintln!("Error: could not load {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        if let Some(entry) = journal.dropped_entry() {
+            eprintln!("Warning: dropping truncated journal entry {:?}", entry);
+        }
+        journal
+    });
+
+    if opts.compact {
+        if let Some(j) = journal.as_mut() {
+            if let Err(e) = j.compact(&ws) {
+                eprintln!("Error: compaction failed: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let run_opts = runner::Options {
+        highlight: if !opts.highlight {
+            Highlight::Off
+        } else if io::stdout().is_terminal() {
+            Highlight::Ansi
+        } else {
+            Highlight::Brackets
+        },
+    };
 
     let stdin = io::stdin();
-    for line in stdin.lock().lines() {
-        if let Ok(l) = line {
-            runner::run_line(&l, &mut tl);
+    let mut lines = stdin
+        .lock()
+        .lines()
+        .map_while(Result::ok)
+        .enumerate()
+        .map(|(n, l)| (n + 1, l));
+
+    // Batch input starts with the number of queries; streaming input is
+    // just queries until the end.
+    let announced = if opts.stream {
+        None
+    } else {
+        match lines.next() {
+            None => return,
+            Some((n, l)) => match parser::parse_query_count(n, &l) {
+                Ok(count) => Some(count),
+                Err(e) => {
+                    eprintln!("Error: {}", QueryError::Parse(e));
+                    eprintln!("Use --stream for input without a leading query count.");
+                    process::exit(1);
+                }
+            },
+        }
+    };
+
+    let mut failed = false;
+    let mut seen: u64 = 0;
+    for (n, l) in lines {
+        if l.trim().is_empty() {
+            continue;
         }
+        seen += 1;
+        if let Some(count) = announced {
+            if seen == count + 1 {
+                eprintln!(
+                    "Warning: line {}: more queries than the {} announced",
+                    n, count
+                );
+            }
+        }
+        match runner::run_line(n, &l, &mut ws, journal.as_mut(), &run_opts) {
+            Ok(()) => {}
+            // Later queries would build on a change the journal lost.
+            Err(QueryError::Journal(_)) => process::exit(1),
+            Err(_) => failed = true,
+        }
+    }
+    if let Some(count) = announced {
+        if seen < count {
+            eprintln!("Warning: expected {} queries, got {}", count, seen);
+        }
+    }
+
+    if failed && opts.strict {
+        process::exit(1);
     }
 }
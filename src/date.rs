@@ -0,0 +1,372 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A day in the proleptic Gregorian calendar. There are no time zones:
+/// the system clock is read as UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    year: i32,
+    month: u32,
+    day: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    /// Days since Monday.
+    pub fn number(self) -> i64 {
+        self as i64
+    }
+}
+
+impl fmt::Display for Weekday {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Weekday::Monday => "monday",
+            Weekday::Tuesday => "tuesday",
+            Weekday::Wednesday => "wednesday",
+            Weekday::Thursday => "thursday",
+            Weekday::Friday => "friday",
+            Weekday::Saturday => "saturday",
+            Weekday::Sunday => "sunday",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+pub fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl Date {
+    pub const EPOCH: Date = Date {
+        year: 1970,
+        month: 1,
+        day: 1,
+    };
+
+    /// The first and last dates that can be written as `YYYY-MM-DD`, and so
+    /// the only ones a journal or snapshot can hold.
+    pub const MIN: Date = Date {
+        year: 0,
+        month: 1,
+        day: 1,
+    };
+    pub const MAX: Date = Date {
+        year: 9999,
+        month: 12,
+        day: 31,
+    };
+
+    /// The date, if there is such a day.
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Date> {
+        if (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month) {
+            Some(Date { year, month, day })
+        } else {
+            None
+        }
+    }
+
+    pub fn year(&self) -> i32 {
+        self.year
+    }
+
+    pub fn month(&self) -> u32 {
+        self.month
+    }
+
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    /// The date `days` days after 1970-01-01.
+    pub fn from_days(days: i64) -> Date {
+        // Howard Hinnant's civil_from_days, with eras of 400 years.
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+        Date { year, month, day }
+    }
+
+    /// Days since 1970-01-01, negative before.
+    pub fn days(&self) -> i64 {
+        let year = i64::from(self.year) - if self.month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let month = i64::from(self.month);
+        let mp = if month > 2 { month - 3 } else { month + 9 };
+        let doy = (153 * mp + 2) / 5 + i64::from(self.day) - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    pub fn add_days(self, n: i64) -> Date {
+        Date::from_days(self.days() + n)
+    }
+
+    /// The same day `n` months later, or the last day of that month if it
+    /// is shorter.
+    pub fn add_months(self, n: u32) -> Date {
+        let months = self.year as i64 * 12 + i64::from(self.month) - 1 + i64::from(n);
+        let year = months.div_euclid(12) as i32;
+        let month = months.rem_euclid(12) as u32 + 1;
+        Date {
+            year,
+            month,
+            day: self.day.min(days_in_month(year, month)),
+        }
+    }
+
+    /// The last day of the month of this date.
+    pub fn end_of_month(self) -> Date {
+        Date {
+            day: days_in_month(self.year, self.month),
+            ..self
+        }
+    }
+
+    /// Whether the date is between `Date::MIN` and `Date::MAX`.
+    pub fn is_writable(self) -> bool {
+        (Date::MIN..=Date::MAX).contains(&self)
+    }
+
+    pub fn weekday(self) -> Weekday {
+        // 1970-01-01 was a Thursday.
+        match (self.days() + 3).rem_euclid(7) {
+            0 => Weekday::Monday,
+            1 => Weekday::Tuesday,
+            2 => Weekday::Wednesday,
+            3 => Weekday::Thursday,
+            4 => Weekday::Friday,
+            5 => Weekday::Saturday,
+            _ => Weekday::Sunday,
+        }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Parses `YYYY-MM-DD`.
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Date, String> {
+        let bad = || format!("{:?} is not a date like 2026-11-01", s);
+        let parts: Vec<&str> = s.split('-').collect();
+        match parts[..] {
+            [y, m, d]
+                if y.len() == 4
+                    && m.len() == 2
+                    && d.len() == 2
+                    && s.chars().all(|c| c.is_ascii_digit() || c == '-') =>
+            {
+                let n = |p: &str| p.parse::<u32>().map_err(|_| bad());
+                Date::new(n(y)? as i32, n(m)?, n(d)?).ok_or_else(bad)
+            }
+            _ => Err(bad()),
+        }
+    }
+}
+
+/// A moment in time, in whole seconds since 1970-01-01T00:00:00Z.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub fn new(seconds: i64) -> Timestamp {
+        Timestamp(seconds)
+    }
+
+    pub fn seconds(&self) -> i64 {
+        self.0
+    }
+
+    pub fn date(self) -> Date {
+        Date::from_days(self.0.div_euclid(86_400))
+    }
+}
+
+/// Midnight at the start of the day.
+impl From<Date> for Timestamp {
+    fn from(d: Date) -> Timestamp {
+        Timestamp(d.days() * 86_400)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.0.rem_euclid(86_400);
+        write!(
+            f,
+            "{}T{:02}:{:02}:{:02}Z",
+            self.date(),
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )
+    }
+}
+
+/// Parses `YYYY-MM-DDTHH:MM:SSZ`.
+impl FromStr for Timestamp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Timestamp, String> {
+        let bad = || format!("{:?} is not a time like 2026-11-01T09:30:00Z", s);
+        let (date, time) = match (s.get(..10), s.get(10..)) {
+            (Some(d), Some(t)) => (d.parse::<Date>().map_err(|_| bad())?, t),
+            _ => return Err(bad()),
+        };
+        let field = |from: usize, max: i64| -> Result<i64, String> {
+            match time.get(from..from + 2) {
+                Some(ds) if ds.chars().all(|c| c.is_ascii_digit()) => {
+                    let n: i64 = ds.parse().map_err(|_| bad())?;
+                    if n <= max {
+                        Ok(n)
+                    } else {
+                        Err(bad())
+                    }
+                }
+                _ => Err(bad()),
+            }
+        };
+        let shape = time.len() == 10
+            && time.starts_with('T')
+            && time.get(3..4) == Some(":")
+            && time.get(6..7) == Some(":")
+            && time.ends_with('Z');
+        if !shape {
+            return Err(bad());
+        }
+        let seconds = field(1, 23)? * 3600 + field(4, 59)? * 60 + field(7, 59)?;
+        Ok(Timestamp(Timestamp::from(date).0 + seconds))
+    }
+}
+
+/// Where the list gets the current time from. Tests fix it to a known moment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    System,
+    Fixed(Timestamp),
+}
+
+impl Clock {
+    pub fn now(&self) -> Timestamp {
+        match self {
+            Clock::System => {
+                let secs = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                Timestamp(secs as i64)
+            }
+            Clock::Fixed(t) => *t,
+        }
+    }
+
+    pub fn today(&self) -> Date {
+        self.now().date()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> Date {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_days_roundtrip() {
+        assert_eq!(Date::EPOCH.days(), 0);
+        assert_eq!(Date::from_days(0), Date::EPOCH);
+        assert_eq!(date("2000-03-01").days(), 11_017);
+        assert_eq!(date("1969-12-31").days(), -1);
+        for days in -800_000..800_000 {
+            assert_eq!(Date::from_days(days).days(), days);
+        }
+    }
+
+    #[test]
+    fn test_calendar() {
+        assert_eq!(date("2026-02-28").add_days(1), date("2026-03-01"));
+        assert_eq!(date("2024-02-28").add_days(1), date("2024-02-29"));
+        assert_eq!(date("2026-12-31").add_days(1), date("2027-01-01"));
+        assert_eq!(date("2026-10-15").weekday(), Weekday::Thursday);
+        assert_eq!(date("2026-10-19").weekday(), Weekday::Monday);
+        assert_eq!(days_in_month(2100, 2), 28);
+        assert_eq!(days_in_month(2000, 2), 29);
+        assert_eq!(date("2024-02-10").end_of_month(), date("2024-02-29"));
+        assert_eq!(date("2026-01-31").add_months(1), date("2026-02-28"));
+        assert_eq!(date("2026-11-15").add_months(3), date("2027-02-15"));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Date::new(2026, 11, 1), Some(date("2026-11-01")));
+        assert_eq!(date("2026-11-01").to_string(), "2026-11-01");
+        for bad in &[
+            "2026-02-30",
+            "2026-13-01",
+            "2026-1-01",
+            "26-11-01",
+            "2026-11-0a",
+            "+026-11-01",
+        ] {
+            assert!(bad.parse::<Date>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let t: Timestamp = "2026-10-15T09:30:05Z".parse().unwrap();
+        assert_eq!(t.date(), date("2026-10-15"));
+        assert_eq!(
+            t.seconds() - Timestamp::from(t.date()).seconds(),
+            9 * 3600 + 30 * 60 + 5
+        );
+        assert_eq!(t.to_string(), "2026-10-15T09:30:05Z");
+        assert_eq!(Timestamp::new(-1).to_string(), "1969-12-31T23:59:59Z");
+        for bad in &[
+            "2026-10-15",
+            "2026-10-15T09:30:05",
+            "2026-10-15T24:00:00Z",
+            "2026-10-15 09:30:05Z",
+            "2026-10-15T9:30:05Z",
+            "2026-10-15T09:30:05ZZ",
+            "2026-10-15T0é:00:0Z",
+        ] {
+            assert!(bad.parse::<Timestamp>().is_err(), "{}", bad);
+        }
+    }
+}
@@ -1,7 +1,13 @@
+pub mod date;
 pub mod parser;
 pub mod query;
 pub mod runner;
+pub mod storage;
 pub mod todo_list;
+pub mod workspace;
 
+pub use date::*;
 pub use query::*;
+pub use storage::*;
 pub use todo_list::*;
+pub use workspace::*;
@@ -2,122 +2,821 @@ use crate::*;
 
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_while, take_while1},
-    character::complete::{digit1, one_of, space0},
-    multi::{many1, separated_list},
-    sequence::{delimited, pair, preceded},
-    IResult,
+    bytes::complete::{is_not, tag, take_while1, take_while_m_n},
+    character::complete::{digit1, one_of, space0, space1},
+    combinator::{all_consuming, cut, map, map_res, not, opt, peek, recognize, verify},
+    error::{context, ErrorKind, VerboseError, VerboseErrorKind},
+    multi::{many0, many1, separated_list, separated_nonempty_list},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    Err,
 };
 
+type IResult<I, O> = nom::IResult<I, O, VerboseError<I>>;
+
+/// Parses one input line into a `Query`, requiring the whole line (up to
+/// trailing whitespace) to be consumed. `line_no` is only used for the error.
+pub fn parse_line(line_no: usize, line: &str) -> Result<Query, ParseError> {
+    parse_all(line_no, line, query)
+}
+
+/// Parses the header of batch input: the number of queries that follow.
+pub fn parse_query_count(line_no: usize, line: &str) -> Result<u64, ParseError> {
+    parse_all(
+        line_no,
+        line,
+        context(
+            "a query count",
+            map_res(digit1, |ds: &str| ds.parse::<u64>()),
+        ),
+    )
+}
+
+fn parse_all<'a, O, F>(line_no: usize, line: &'a str, parser: F) -> Result<O, ParseError>
+where
+    F: Fn(&'a str) -> IResult<&'a str, O>,
+{
+    match all_consuming(terminated(preceded(space0, parser), space0))(line) {
+        Ok((_, o)) => Ok(o),
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => Err(to_parse_error(line_no, line, e)),
+        Err(Err::Incomplete(_)) => Err(ParseError {
+            line: line_no,
+            column: line.chars().count() + 1,
+            expected: String::from("more input"),
+            found: String::from("end of line"),
+        }),
+    }
+}
+
+fn to_parse_error(line_no: usize, line: &str, e: VerboseError<&str>) -> ParseError {
+    // The first entry is where parsing actually stopped, the first context
+    // is the innermost thing we were trying to read there.
+    let rest = e.errors.first().map(|(i, _)| *i).unwrap_or(line);
+    let expected = e
+        .errors
+        .iter()
+        .filter_map(|(_, kind)| match kind {
+            VerboseErrorKind::Context(ctx) => Some(*ctx),
+            _ => None,
+        })
+        .next()
+        .unwrap_or("end of line");
+    let consumed = &line[..line.len() - rest.len()];
+    let found = rest.split_whitespace().next().unwrap_or("end of line");
+
+    ParseError {
+        line: line_no,
+        column: consumed.chars().count() + 1,
+        expected: expected.to_string(),
+        found: found.to_string(),
+    }
+}
+
 pub fn query(input: &str) -> IResult<&str, Query> {
-    alt((add, done, search))(input)
+    context(
+        "a command",
+        alt((
+            add, done, undone, delete, edit, search, tree, blocks, use_list, lists, move_item,
+            tag_tree, tags_query, tag_rename, tag_merge, retag,
+        )),
+    )(input)
+}
+
+/// A command name, which must not run on into a longer word.
+fn keyword<'a>(k: &'static str) -> impl Fn(&'a str) -> IResult<&'a str, &'a str> {
+    move |input: &'a str| {
+        let (rest, kw) = tag(k)(input)?;
+        match rest.chars().next() {
+            Some(c) if c.is_alphanumeric() || c == '-' => Err(Err::Error(VerboseError {
+                errors: vec![(input, VerboseErrorKind::Nom(ErrorKind::Tag))],
+            })),
+            _ => Ok((rest, kw)),
+        }
+    }
 }
 
 fn ws(input: &str) -> IResult<&str, char> {
-    one_of(" \t")(input)
+    context("a space", one_of(" \t"))(input)
 }
 
 fn add(input: &str) -> IResult<&str, Query> {
     match preceded(
-        pair(tag("add"), ws),
-        pair(description, preceded(space0, tags)),
+        keyword("add"),
+        cut(preceded(
+            ws,
+            pair(description, preceded(space0, attributes)),
+        )),
     )(input)
     {
         Err(e) => Err(e),
-        Ok((rest, (d, ts))) => Ok((rest, Query::Add(Description::new(&d), ts))),
+        Ok((rest, (d, (ts, attrs)))) => Ok((rest, Query::Add(Description::new(&d), ts, attrs))),
     }
 }
 
-fn is_lowecase_or_dash_or_whitespace(c: char) -> bool {
-    c.is_ascii_lowercase() || c.is_whitespace() || c == '-'
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-'
 }
 
-fn is_lowecase_or_dash(c: char) -> bool {
-    c.is_ascii_lowercase() || c == '-'
+fn word(input: &str) -> IResult<&str, &str> {
+    take_while1(is_word_char)(input)
 }
 
-fn sentence(input: &str) -> IResult<&str, &str> {
-    take_while(is_lowecase_or_dash_or_whitespace)(input)
+/// `#tag`, or a path of levels like `#work/backend/auth`.
+fn todo_tag(input: &str) -> IResult<&str, &str> {
+    preceded(tag("#"), tag_path)(input)
 }
 
-fn word(input: &str) -> IResult<&str, &str> {
-    take_while1(is_lowecase_or_dash)(input)
+fn tag_path(input: &str) -> IResult<&str, &str> {
+    recognize(separated_nonempty_list(tag("/"), word))(input)
 }
 
-fn todo_tag(input: &str) -> IResult<&str, &str> {
-    preceded(tag("#"), word)(input)
+/// A tag in the tag management commands, where the `#` is optional.
+fn tag_name(input: &str) -> IResult<&str, Tag> {
+    context("a tag", map(preceded(opt(tag("#")), tag_path), Tag::new))(input)
 }
 
-fn description(input: &str) -> IResult<&str, String> {
-    match delimited(tag("\""), sentence, tag("\""))(input) {
-        Err(e) => Err(e),
-        Ok((rest, d)) => Ok((rest, d.to_string())),
-    }
+pub(crate) fn description(input: &str) -> IResult<&str, String> {
+    context(
+        "a quoted description",
+        delimited(
+            tag("\""),
+            description_text,
+            context("a closing quote", tag("\"")),
+        ),
+    )(input)
+}
+
+/// Anything up to the closing quote, where `\"` and `\\` stand for a
+/// quote and a backslash.
+fn description_text(input: &str) -> IResult<&str, String> {
+    map(
+        many0(alt((
+            is_not("\"\\"),
+            preceded(
+                tag("\\"),
+                cut(context("an escape sequence", alt((tag("\""), tag("\\"))))),
+            ),
+        ))),
+        |parts| parts.concat(),
+    )(input)
 }
 
-fn tags(input: &str) -> IResult<&str, Vec<Tag>> {
+pub(crate) fn tags(input: &str) -> IResult<&str, Vec<Tag>> {
     match separated_list(ws, todo_tag)(input) {
         Err(e) => Err(e),
         Ok((rest, ts)) => Ok((rest, ts.iter().map(|w| Tag::new(w)).collect())),
     }
 }
 
+enum Attribute {
+    Tag(Tag),
+    Priority(Priority),
+    Due(DateExpr),
+    Recurrence(Recurrence),
+    Parent(Index),
+    After(Index),
+    Clear(AttributeKind),
+}
+
+/// The tags and attributes after a description, in any order. If an
+/// attribute is given twice, the last one counts, except for `after:`
+/// which may be given for each item waited for. Each attribute may also
+/// be given as `none`, e.g. `due:none`, to clear it in an edit.
+pub(crate) fn attributes(input: &str) -> IResult<&str, (Vec<Tag>, Attributes)> {
+    let (rest, attrs) = separated_list(
+        ws,
+        alt((
+            map(todo_tag, |t| Attribute::Tag(Tag::new(t))),
+            preceded(
+                tag("!"),
+                cut(or_none(
+                    "a priority",
+                    priority,
+                    AttributeKind::Priority,
+                    Attribute::Priority,
+                )),
+            ),
+            preceded(
+                tag("due:"),
+                cut(or_none(
+                    "a date",
+                    date_expr,
+                    AttributeKind::Due,
+                    Attribute::Due,
+                )),
+            ),
+            preceded(
+                tag("every:"),
+                cut(or_none(
+                    "a recurrence",
+                    recurrence,
+                    AttributeKind::Recurrence,
+                    Attribute::Recurrence,
+                )),
+            ),
+            preceded(
+                tag("parent:"),
+                cut(or_none(
+                    "an index",
+                    index,
+                    AttributeKind::Parent,
+                    Attribute::Parent,
+                )),
+            ),
+            preceded(
+                tag("after:"),
+                cut(or_none(
+                    "an index",
+                    index,
+                    AttributeKind::After,
+                    Attribute::After,
+                )),
+            ),
+        )),
+    )(input)?;
+    let mut tags = vec![];
+    let mut attributes = Attributes::default();
+    for attr in attrs {
+        let given = match attr {
+            Attribute::Priority(_) => Some(AttributeKind::Priority),
+            Attribute::Due(_) => Some(AttributeKind::Due),
+            Attribute::Recurrence(_) => Some(AttributeKind::Recurrence),
+            Attribute::Parent(_) => Some(AttributeKind::Parent),
+            _ => None,
+        };
+        attributes.cleared.retain(|&k| Some(k) != given);
+        match attr {
+            Attribute::Tag(t) => tags.push(t),
+            Attribute::Priority(p) => attributes.priority = Some(p),
+            Attribute::Due(d) => attributes.due = Some(d),
+            Attribute::Recurrence(r) => attributes.recurrence = Some(r),
+            Attribute::Parent(i) => attributes.parent = Some(i),
+            Attribute::After(i) if !attributes.after.contains(&i) => attributes.after.push(i),
+            Attribute::After(_) => {}
+            Attribute::Clear(kind) => {
+                match kind {
+                    AttributeKind::Priority => attributes.priority = None,
+                    AttributeKind::Due => attributes.due = None,
+                    AttributeKind::Recurrence => attributes.recurrence = None,
+                    AttributeKind::Parent => attributes.parent = None,
+                    AttributeKind::After => attributes.after.clear(),
+                }
+                if !attributes.clears(kind) {
+                    attributes.cleared.push(kind);
+                }
+            }
+        }
+    }
+    Ok((rest, (tags, attributes)))
+}
+
+/// `none`, or what `parser` reads, as an `Attribute`.
+fn or_none<'a, O, P, A>(
+    name: &'static str,
+    parser: P,
+    kind: AttributeKind,
+    attribute: A,
+) -> impl Fn(&'a str) -> IResult<&'a str, Attribute>
+where
+    P: Fn(&'a str) -> IResult<&'a str, O>,
+    A: Fn(O) -> Attribute,
+{
+    context(
+        name,
+        alt((
+            map(keyword("none"), move |_| Attribute::Clear(kind)),
+            map(parser, attribute),
+        )),
+    )
+}
+
+fn priority(input: &str) -> IResult<&str, Priority> {
+    context(
+        "a priority",
+        alt((
+            map(keyword("low"), |_| Priority::Low),
+            map(keyword("medium"), |_| Priority::Medium),
+            map(keyword("high"), |_| Priority::High),
+        )),
+    )(input)
+}
+
+/// An ISO date, or one relative to today: `today`, `tomorrow`,
+/// `next-friday`, `+3d`, `+2w` or `eom` for the end of the month.
+fn date_expr(input: &str) -> IResult<&str, DateExpr> {
+    context(
+        "a date",
+        alt((
+            map(date, DateExpr::On),
+            map(keyword("today"), |_| DateExpr::Today),
+            map(keyword("tomorrow"), |_| DateExpr::Tomorrow),
+            map(preceded(tag("next-"), weekday), DateExpr::Next),
+            map(delimited(tag("+"), count, keyword("d")), DateExpr::InDays),
+            map(delimited(tag("+"), count, keyword("w")), DateExpr::InWeeks),
+            map(keyword("eom"), |_| DateExpr::EndOfMonth),
+        )),
+    )(input)
+}
+
+/// `day`, `week`, `month`, `<n>d`, `<n>w` or `month-on-<day>`.
+fn recurrence(input: &str) -> IResult<&str, Recurrence> {
+    let positive = |input| verify(count, |n: &u32| *n > 0)(input);
+    context(
+        "a recurrence",
+        alt((
+            map(keyword("day"), |_| Recurrence::Days(1)),
+            map(keyword("week"), |_| Recurrence::Weeks(1)),
+            map(
+                preceded(
+                    tag("month-on-"),
+                    verify(count, |d: &u32| (1..=31).contains(d)),
+                ),
+                Recurrence::MonthlyOn,
+            ),
+            map(keyword("month"), |_| Recurrence::Monthly),
+            map(terminated(positive, keyword("d")), Recurrence::Days),
+            map(terminated(positive, keyword("w")), Recurrence::Weeks),
+        )),
+    )(input)
+}
+
+fn count(input: &str) -> IResult<&str, u32> {
+    map_res(digit1, |ds: &str| ds.parse::<u32>())(input)
+}
+
+fn weekday(input: &str) -> IResult<&str, Weekday> {
+    alt((
+        map(keyword("monday"), |_| Weekday::Monday),
+        map(keyword("tuesday"), |_| Weekday::Tuesday),
+        map(keyword("wednesday"), |_| Weekday::Wednesday),
+        map(keyword("thursday"), |_| Weekday::Thursday),
+        map(keyword("friday"), |_| Weekday::Friday),
+        map(keyword("saturday"), |_| Weekday::Saturday),
+        map(keyword("sunday"), |_| Weekday::Sunday),
+    ))(input)
+}
+
+/// `YYYY-MM-DD`, which has to be a real day.
+fn date(input: &str) -> IResult<&str, Date> {
+    let digits = |n| take_while_m_n(n, n, |c: char| c.is_ascii_digit());
+    map_res(
+        recognize(tuple((digits(4), tag("-"), digits(2), tag("-"), digits(2)))),
+        str::parse,
+    )(input)
+}
+
 fn done(input: &str) -> IResult<&str, Query> {
-    match preceded(pair(tag("done"), ws), many1(digit1))(input) {
+    match preceded(keyword("done"), cut(preceded(ws, index)))(input) {
         Err(e) => Err(e),
-        Ok((rest, i)) => Ok((rest, Query::Done(Index::new(vec_to_u64(i))))),
+        Ok((rest, i)) => Ok((rest, Query::Done(i))),
     }
 }
 
-fn vec_to_u64(dss: Vec<&str>) -> u64 {
-    let ds = dss
-        .iter()
-        .fold("".to_string(), |acc, x| format!("{}{}", acc, x));
-    ds.parse::<u64>().unwrap()
+fn undone(input: &str) -> IResult<&str, Query> {
+    map(
+        preceded(keyword("undone"), cut(preceded(ws, index))),
+        Query::Undone,
+    )(input)
+}
+
+fn delete(input: &str) -> IResult<&str, Query> {
+    map(
+        preceded(keyword("delete"), cut(preceded(ws, index))),
+        Query::Delete,
+    )(input)
+}
+
+fn edit(input: &str) -> IResult<&str, Query> {
+    alt((
+        map(
+            preceded(
+                keyword("edit"),
+                cut(pair(
+                    preceded(ws, index),
+                    preceded(ws, pair(description, preceded(space0, attributes))),
+                )),
+            ),
+            |(i, (d, (ts, attrs)))| Query::Edit(i, Edit::All(Description::new(&d), ts, attrs)),
+        ),
+        map(
+            preceded(
+                keyword("edit-description"),
+                cut(pair(preceded(ws, index), preceded(ws, description))),
+            ),
+            |(i, d)| Query::Edit(i, Edit::Description(Description::new(&d))),
+        ),
+        map(
+            preceded(
+                keyword("edit-tags"),
+                cut(pair(preceded(ws, index), preceded(space0, tags))),
+            ),
+            |(i, ts)| Query::Edit(i, Edit::Tags(ts)),
+        ),
+        map(
+            preceded(
+                keyword("edit-priority"),
+                cut(pair(
+                    preceded(ws, index),
+                    preceded(
+                        ws,
+                        alt((map(priority, Some), map(keyword("none"), |_| None))),
+                    ),
+                )),
+            ),
+            |(i, p)| Query::Edit(i, Edit::Priority(p)),
+        ),
+    ))(input)
+}
+
+fn index(input: &str) -> IResult<&str, Index> {
+    match context("an index", map_res(digit1, |ds: &str| ds.parse::<u64>()))(input) {
+        Err(e) => Err(e),
+        Ok((rest, i)) => Ok((rest, Index::new(i))),
+    }
+}
+
+/// A `TodoItem` as written to a snapshot: `<index> <open|done> "<description>"
+/// #tags !priority due:YYYY-MM-DD every:<recurrence> parent:<index> after:<index>...
+/// created:<timestamp> completed:<timestamp> series:<index> next:<index>`.
+pub fn snapshot_item(input: &str) -> IResult<&str, TodoItem> {
+    match tuple((
+        pair(index, preceded(ws, alt((tag("open"), tag("done"))))),
+        preceded(ws, pair(description, preceded(space0, attributes))),
+        opt(preceded(pair(space0, tag("created:")), timestamp)),
+        opt(preceded(pair(space0, tag("completed:")), timestamp)),
+        opt(preceded(pair(space0, tag("series:")), index)),
+        opt(preceded(pair(space0, tag("next:")), index)),
+    ))(input)
+    {
+        Err(e) => Err(e),
+        Ok((rest, ((i, status), (d, (ts, attrs)), created, completed, series, next))) => {
+            let mut item = TodoItem::create(i, &Description::new(&d), &ts, status == "done");
+            // Snapshots only hold resolved dates, so any day will do.
+            item.set_attributes(&attrs, Date::EPOCH);
+            item.created_at = created;
+            item.completed_at = completed;
+            item.series = series;
+            item.next = next;
+            Ok((rest, item))
+        }
+    }
 }
 
-#[derive(Debug)]
-enum SearchWordOrTag {
-    RawWord(String),
-    RawTag(String),
+/// `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn timestamp(input: &str) -> IResult<&str, Timestamp> {
+    let digits = |n| take_while_m_n(n, n, |c: char| c.is_ascii_digit());
+    context(
+        "a timestamp",
+        map_res(
+            recognize(tuple((
+                date,
+                tag("T"),
+                digits(2),
+                tag(":"),
+                digits(2),
+                tag(":"),
+                digits(2),
+                tag("Z"),
+            ))),
+            str::parse,
+        ),
+    )(input)
 }
 
 fn search(input: &str) -> IResult<&str, Query> {
     match preceded(
-        pair(tag("search"), ws),
-        separated_list(tag(" "), search_word_or_tag),
+        keyword("search"),
+        cut(pair(
+            many0(preceded(space1, search_flag)),
+            preceded(space0, opt(|i| search_expr(i, 0))),
+        )),
     )(input)
     {
         Err(e) => Err(e),
-        Ok((rest, mash)) => Ok((rest, mash_to_query(mash))),
+        Ok((rest, (flags, expr))) => {
+            let mut params = SearchParams {
+                expr: expr.unwrap_or_else(|| SearchExpr::And(vec![])),
+                mode: MatchMode::default(),
+                status: StatusFilter::default(),
+                rank: false,
+                by_priority: false,
+                all_lists: false,
+            };
+            for flag in flags {
+                match flag {
+                    SearchFlag::Mode(m) => params.mode = m,
+                    SearchFlag::Status(s) => params.status = s,
+                    SearchFlag::Rank => params.rank = true,
+                    SearchFlag::ByPriority => params.by_priority = true,
+                    SearchFlag::AllLists => params.all_lists = true,
+                }
+            }
+            Ok((rest, Query::Search(params)))
+        }
+    }
+}
+
+/// `blocks <blocker> <blocked>`.
+fn blocks(input: &str) -> IResult<&str, Query> {
+    map(
+        preceded(
+            keyword("blocks"),
+            cut(pair(preceded(ws, index), preceded(ws, index))),
+        ),
+        |(blocker, blocked)| Query::Block(blocker, blocked),
+    )(input)
+}
+
+fn list_name(input: &str) -> IResult<&str, String> {
+    context("a list name", map(word, str::to_owned))(input)
+}
+
+fn use_list(input: &str) -> IResult<&str, Query> {
+    map(
+        preceded(keyword("use"), cut(preceded(ws, list_name))),
+        Query::Use,
+    )(input)
+}
+
+fn tag_tree(input: &str) -> IResult<&str, Query> {
+    map(keyword("tag-tree"), |_| Query::TagTree)(input)
+}
+
+fn tags_query(input: &str) -> IResult<&str, Query> {
+    map(keyword("tags"), |_| Query::Tags)(input)
+}
+
+/// `tag-rename <old> <new>`.
+fn tag_rename(input: &str) -> IResult<&str, Query> {
+    map(
+        preceded(
+            keyword("tag-rename"),
+            cut(pair(preceded(ws, tag_name), preceded(ws, tag_name))),
+        ),
+        |(from, to)| Query::RenameTag(from, to),
+    )(input)
+}
+
+/// `tag-merge <tag>... into <tag>`.
+fn tag_merge(input: &str) -> IResult<&str, Query> {
+    map(
+        preceded(
+            keyword("tag-merge"),
+            cut(pair(
+                many1(preceded(
+                    ws,
+                    context("a tag", preceded(not(keyword("into")), tag_name)),
+                )),
+                preceded(
+                    pair(ws, context("\"into\"", keyword("into"))),
+                    preceded(ws, tag_name),
+                ),
+            )),
+        ),
+        |(from, to)| Query::MergeTags(from, to),
+    )(input)
+}
+
+/// `tag <index> +#add -#remove...`.
+fn retag(input: &str) -> IResult<&str, Query> {
+    map(
+        preceded(
+            keyword("tag"),
+            cut(pair(
+                preceded(ws, index),
+                many1(preceded(
+                    ws,
+                    context(
+                        "a tag change like +#tag or -#tag",
+                        alt((
+                            map(preceded(tag("+"), todo_tag), |t| {
+                                TagChange::Add(Tag::new(t))
+                            }),
+                            map(preceded(tag("-"), todo_tag), |t| {
+                                TagChange::Remove(Tag::new(t))
+                            }),
+                        )),
+                    ),
+                )),
+            )),
+        ),
+        |(i, changes)| Query::Retag(i, changes),
+    )(input)
+}
+
+fn lists(input: &str) -> IResult<&str, Query> {
+    map(keyword("lists"), |_| Query::Lists)(input)
+}
+
+/// `move <index> <list>`.
+fn move_item(input: &str) -> IResult<&str, Query> {
+    map(
+        preceded(
+            keyword("move"),
+            cut(pair(preceded(ws, index), preceded(ws, list_name))),
+        ),
+        |(i, name)| Query::Move(i, name),
+    )(input)
+}
+
+/// `tree`, optionally with `--all` or `--done-only`.
+fn tree(input: &str) -> IResult<&str, Query> {
+    map(
+        preceded(
+            keyword("tree"),
+            opt(preceded(
+                pair(space1, tag("--")),
+                cut(context(
+                    "a tree flag",
+                    alt((
+                        map(keyword("all"), |_| StatusFilter::All),
+                        map(keyword("done-only"), |_| StatusFilter::Done),
+                    )),
+                )),
+            )),
+        ),
+        |status| Query::Tree(status.unwrap_or_default()),
+    )(input)
+}
+
+enum SearchFlag {
+    Mode(MatchMode),
+    Status(StatusFilter),
+    Rank,
+    ByPriority,
+    AllLists,
+}
+
+fn search_flag(input: &str) -> IResult<&str, SearchFlag> {
+    preceded(
+        tag("--"),
+        cut(context(
+            "a search flag",
+            alt((
+                map(keyword("subsequence"), |_| {
+                    SearchFlag::Mode(MatchMode::Subsequence)
+                }),
+                map(keyword("prefix"), |_| SearchFlag::Mode(MatchMode::Prefix)),
+                map(keyword("substring"), |_| {
+                    SearchFlag::Mode(MatchMode::Substring)
+                }),
+                map(keyword("exact"), |_| SearchFlag::Mode(MatchMode::Exact)),
+                map(keyword("all"), |_| SearchFlag::Status(StatusFilter::All)),
+                map(keyword("done-only"), |_| {
+                    SearchFlag::Status(StatusFilter::Done)
+                }),
+                map(keyword("rank"), |_| SearchFlag::Rank),
+                map(keyword("by-priority"), |_| SearchFlag::ByPriority),
+                map(keyword("all-lists"), |_| SearchFlag::AllLists),
+            )),
+        )),
+    )(input)
+}
+
+// Search expressions, loosest binding first. Like in web search engines,
+// `or` binds tighter than the implicit `and`, so `buy or call not #work`
+// means `(buy or call) and not #work`:
+//   expr  := or (["and"] or)*
+//   or    := unary ("or" unary)*
+//   unary := "not" unary | "-#" tag | "(" expr ")" | filter | #tag | word
+//   filter := "priority" cmp priority | "is:" ("overdue" | "ready" | "blocked")
+//           | ("due" | "created" | "completed") (cmp date | ":" (period | date))
+//   cmp   := "<" | "<=" | "=" | ">=" | ">"
+//
+// `depth` counts the `not`s and parentheses around the term being read, so
+// that a line can't nest deep enough to overflow the stack.
+
+/// How deeply `not` and parentheses may nest in a search.
+const MAX_SEARCH_DEPTH: usize = 64;
+
+fn search_expr(input: &str, depth: usize) -> IResult<&str, SearchExpr> {
+    let (rest, first) = search_or(input, depth)?;
+    let (rest, others) = many0(preceded(
+        pair(space1, opt(pair(keyword("and"), space1))),
+        |i| search_or(i, depth),
+    ))(rest)?;
+    Ok((rest, SearchExpr::and(first, others)))
+}
+
+fn search_or(input: &str, depth: usize) -> IResult<&str, SearchExpr> {
+    let (rest, first) = search_unary(input, depth)?;
+    let (rest, others) = many0(preceded(
+        delimited(space1, keyword("or"), space1),
+        cut(context("a search term", |i| search_unary(i, depth))),
+    ))(rest)?;
+    Ok((rest, SearchExpr::or(first, others)))
+}
+
+fn search_unary(input: &str, depth: usize) -> IResult<&str, SearchExpr> {
+    if depth > MAX_SEARCH_DEPTH {
+        return Err(Err::Failure(VerboseError {
+            errors: vec![(
+                input,
+                VerboseErrorKind::Context("a search that nests less deeply"),
+            )],
+        }));
+    }
+    alt((
+        map(
+            preceded(
+                pair(keyword("not"), space1),
+                cut(context("a search term", |i| search_unary(i, depth + 1))),
+            ),
+            |e| SearchExpr::Not(Box::new(e)),
+        ),
+        map(preceded(tag("-"), todo_tag), |t| {
+            SearchExpr::Not(Box::new(SearchExpr::Tag(Tag::new(t))))
+        }),
+        preceded(
+            tag("("),
+            cut(terminated(
+                delimited(space0, |i| search_expr(i, depth + 1), space0),
+                context("a closing parenthesis", tag(")")),
+            )),
+        ),
+        search_filter,
+        search_word_or_tag,
+    ))(input)
+}
+
+fn search_filter(input: &str) -> IResult<&str, SearchExpr> {
+    alt((
+        map(
+            preceded(
+                terminated(tag("priority"), peek(one_of("<=>"))),
+                cut(pair(comparison, priority)),
+            ),
+            |(cmp, p)| SearchExpr::Priority(cmp, p),
+        ),
+        date_filter("due", DateField::Due),
+        date_filter("created", DateField::Created),
+        date_filter("completed", DateField::Completed),
+        preceded(
+            tag("is:"),
+            cut(context(
+                "overdue, ready or blocked",
+                alt((
+                    map(keyword("overdue"), |_| SearchExpr::Overdue),
+                    map(keyword("ready"), |_| SearchExpr::Ready),
+                    map(keyword("blocked"), |_| SearchExpr::Blocked),
+                )),
+            )),
+        ),
+    ))(input)
+}
+
+/// `<name><comparison><date>`, or `<name>:` and a period or a date.
+fn date_filter<'a>(
+    name: &'static str,
+    field: DateField,
+) -> impl Fn(&'a str) -> IResult<&'a str, SearchExpr> {
+    move |input: &'a str| {
+        let (rest, _) = tag(name)(input)?;
+        alt((
+            preceded(
+                peek(one_of("<=>")),
+                cut(map(pair(comparison, date_expr), |(cmp, d)| {
+                    SearchExpr::Date(field, cmp, d)
+                })),
+            ),
+            preceded(
+                tag(":"),
+                cut(alt((
+                    map(period, |p| SearchExpr::DateWithin(field, p)),
+                    map(date_expr, |d| SearchExpr::Date(field, Comparison::Equal, d)),
+                ))),
+            ),
+        ))(rest)
     }
 }
 
-fn search_word_or_tag(input: &str) -> IResult<&str, SearchWordOrTag> {
+fn period(input: &str) -> IResult<&str, Period> {
+    map(keyword("this-week"), |_| Period::ThisWeek)(input)
+}
+
+fn comparison(input: &str) -> IResult<&str, Comparison> {
+    context(
+        "a comparison",
+        alt((
+            map(tag("<="), |_| Comparison::LessOrEqual),
+            map(tag(">="), |_| Comparison::GreaterOrEqual),
+            map(tag("<"), |_| Comparison::Less),
+            map(tag(">"), |_| Comparison::Greater),
+            map(tag("="), |_| Comparison::Equal),
+        )),
+    )(input)
+}
+
+fn search_word_or_tag(input: &str) -> IResult<&str, SearchExpr> {
     match todo_tag(input) {
-        Err(_) => match word(input) {
+        Err(_) => match verify(word, |w: &str| !SEARCH_KEYWORDS.contains(&w))(input) {
             Err(e) => Err(e),
-            Ok((rest, w)) => Ok((rest, SearchWordOrTag::RawWord(w.to_string()))),
-        
+            Ok((rest, w)) => Ok((rest, SearchExpr::Word(SearchWord::new(w)))),
         },
-        Ok((rest, t)) => Ok((rest, SearchWordOrTag::RawTag(t.to_string()))),
+        Ok((rest, t)) => Ok((rest, SearchExpr::Tag(Tag::new(t)))),
     }
 }
 
-fn mash_to_query(mash: Vec<SearchWordOrTag>) -> Query {
-    let mut search_words: Vec<SearchWord> = vec![];
-    let mut tags: Vec<Tag> = vec![];
-    for i in mash {
-        match i {
-            SearchWordOrTag::RawWord(w) => search_words.push(SearchWord::new(&w)),
-            SearchWordOrTag::RawTag(t) => tags.push(Tag::new(&t)),
-        }
-    }
-    Query::Search(SearchParams {
-        words: search_words,
-        tags,
-    })
-}
+const SEARCH_KEYWORDS: [&str; 3] = ["and", "or", "not"];
 
 #[cfg(test)]
 mod tests {
@@ -127,4 +826,572 @@ mod tests {
     fn test_search_word_or_tag() {
         println!("{:?}", search_word_or_tag("#sometag"))
     }
+
+    fn parse_error(line: &str) -> (usize, String, String) {
+        let e = parse_line(1, line).unwrap_err();
+        (e.column, e.expected, e.found)
+    }
+
+    #[test]
+    fn test_parse_line_errors() {
+        assert_eq!(
+            parse_error("ad \"x\""),
+            (1, "a command".to_string(), "ad".to_string())
+        );
+        assert_eq!(
+            parse_error("done abc"),
+            (6, "an index".to_string(), "abc".to_string())
+        );
+        assert_eq!(
+            parse_error("add buy"),
+            (5, "a quoted description".to_string(), "buy".to_string())
+        );
+        assert_eq!(
+            parse_error("add \"buy"),
+            (9, "a closing quote".to_string(), "end of line".to_string())
+        );
+        assert_eq!(
+            parse_error("done 1 2"),
+            (8, "end of line".to_string(), "2".to_string())
+        );
+        assert_eq!(
+            parse_error("done"),
+            (5, "a space".to_string(), "end of line".to_string())
+        );
+        assert_eq!(
+            parse_error("searching"),
+            (1, "a command".to_string(), "searching".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unicode_words() {
+        assert_eq!(
+            parse_line(1, "add \"Café mit Bob\" #q3 #Straße"),
+            Ok(Query::Add(
+                Description::new("Café mit Bob"),
+                Tag::from_strings(vec!["q3", "Straße"]),
+                Attributes::default()
+            ))
+        );
+        assert_eq!(
+            parse_line(1, "search Café #Q3").unwrap().to_string(),
+            "search Café #Q3"
+        );
+    }
+
+    #[test]
+    fn test_description_escapes() {
+        assert_eq!(
+            parse_line(1, "add \"email: re q3 (urgent), 2pm\""),
+            Ok(Query::Add(
+                Description::new("email: re q3 (urgent), 2pm"),
+                vec![],
+                Attributes::default()
+            ))
+        );
+        let line = "add \"say \\\"hi\\\" to C:\\\\temp\" #misc";
+        assert_eq!(
+            parse_line(1, line),
+            Ok(Query::Add(
+                Description::new("say \"hi\" to C:\\temp"),
+                Tag::from_strings(vec!["misc"]),
+                Attributes::default()
+            ))
+        );
+        assert_eq!(parse_line(1, line).unwrap().to_string(), line);
+        assert_eq!(
+            parse_error("add \"tab\\t\""),
+            (10, "an escape sequence".to_string(), "t\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_priorities() {
+        assert_eq!(
+            parse_line(1, "add \"fix login\" !high #bug"),
+            Ok(Query::Add(
+                Description::new("fix login"),
+                Tag::from_strings(vec!["bug"]),
+                Attributes {
+                    priority: Some(Priority::High),
+                    ..Attributes::default()
+                }
+            ))
+        );
+        assert_eq!(
+            parse_error("add \"fix login\" !urgent"),
+            (18, "a priority".to_string(), "urgent".to_string())
+        );
+        assert_eq!(
+            parse_line(1, "search --by-priority priority>=medium or priority<low")
+                .unwrap()
+                .to_string(),
+            "search --by-priority priority>=medium or priority<low"
+        );
+        assert_eq!(
+            parse_line(1, "search priority"),
+            parse_line(1, "search --subsequence priority")
+        );
+        assert_eq!(
+            parse_error("search priority=urgent"),
+            (17, "a priority".to_string(), "urgent".to_string())
+        );
+    }
+
+    #[test]
+    fn test_due_dates() {
+        let on = |s: &str| DateExpr::On(s.parse().unwrap());
+        assert_eq!(
+            parse_line(1, "add \"pay rent\" due:2026-11-01 #home"),
+            Ok(Query::Add(
+                Description::new("pay rent"),
+                Tag::from_strings(vec!["home"]),
+                Attributes {
+                    due: Some(on("2026-11-01")),
+                    ..Attributes::default()
+                }
+            ))
+        );
+        assert_eq!(
+            parse_error("add \"pay rent\" due:2026-02-30"),
+            (20, "a date".to_string(), "2026-02-30".to_string())
+        );
+        assert_eq!(
+            parse_line(1, "add \"pay rent\" due:today")
+                .unwrap()
+                .resolve("2026-10-15".parse().unwrap())
+                .unwrap()
+                .to_string(),
+            "add \"pay rent\" due:2026-10-15"
+        );
+        for line in &[
+            "search due<2026-11-01",
+            "search due>=today or is:overdue",
+            "search due:this-week",
+            "search due=2026-11-01",
+        ] {
+            assert_eq!(parse_line(1, line).unwrap().to_string(), *line);
+        }
+        assert_eq!(
+            parse_line(1, "search due:2026-11-01").unwrap().to_string(),
+            "search due=2026-11-01"
+        );
+        assert_eq!(
+            parse_error("search due:next-year"),
+            (12, "a date".to_string(), "next-year".to_string())
+        );
+    }
+
+    #[test]
+    fn test_created_and_completed_filters() {
+        for line in &[
+            "search created>2026-10-01",
+            "search --done-only completed=today",
+            "search created:this-week not completed<=+1d",
+        ] {
+            assert_eq!(parse_line(1, line).unwrap().to_string(), *line);
+        }
+        assert_eq!(
+            parse_line(1, "search created"),
+            parse_line(1, "search --subsequence created")
+        );
+        assert_eq!(
+            parse_error("search completed:soon"),
+            (18, "a date".to_string(), "soon".to_string())
+        );
+    }
+
+    #[test]
+    fn test_subtasks() {
+        assert_eq!(
+            parse_line(1, "add \"write tests\" #dev parent:12"),
+            Ok(Query::Add(
+                Description::new("write tests"),
+                Tag::from_strings(vec!["dev"]),
+                Attributes {
+                    parent: Some(Index::new(12)),
+                    ..Attributes::default()
+                }
+            ))
+        );
+        for line in &[
+            "add \"write tests\" #dev parent:12",
+            "edit 3 \"unit tests\" parent:1",
+            "tree",
+            "tree --all",
+            "tree --done-only",
+        ] {
+            assert_eq!(parse_line(1, line).unwrap().to_string(), *line);
+        }
+        assert_eq!(parse_error("add \"x\" parent:me").1, "an index");
+        assert_eq!(parse_error("tree --rank").1, "a tree flag");
+    }
+
+    #[test]
+    fn test_hierarchical_tags() {
+        assert_eq!(
+            parse_line(1, "add \"fix login\" #work/backend/auth #home"),
+            Ok(Query::Add(
+                Description::new("fix login"),
+                Tag::from_strings(vec!["work/backend/auth", "home"]),
+                Attributes::default()
+            ))
+        );
+        for line in &["search #work/backend not #work/backend/auth", "tag-tree"] {
+            assert_eq!(parse_line(1, line).unwrap().to_string(), *line);
+        }
+        assert_eq!(
+            parse_error("add \"x\" #work/"),
+            (14, "end of line".to_string(), "/".to_string())
+        );
+        assert_eq!(parse_error("search #work//x").2, "//x");
+    }
+
+    #[test]
+    fn test_tag_management() {
+        assert_eq!(
+            parse_line(1, "tag-merge a #b into c"),
+            Ok(Query::MergeTags(
+                Tag::from_strings(vec!["a", "b"]),
+                Tag::new("c")
+            ))
+        );
+        assert_eq!(
+            parse_line(1, "tag 3 +#x -#y"),
+            Ok(Query::Retag(
+                Index::new(3),
+                vec![
+                    TagChange::Add(Tag::new("x")),
+                    TagChange::Remove(Tag::new("y"))
+                ]
+            ))
+        );
+        for line in &[
+            "tags",
+            "tag-rename work job",
+            "tag-rename work/backend work/api",
+            "tag-merge shop errands into chores",
+            "tag 0 +#home -#work/backend",
+        ] {
+            assert_eq!(parse_line(1, line).unwrap().to_string(), *line);
+        }
+        assert_eq!(
+            parse_line(1, "tag-rename #a #b").unwrap().to_string(),
+            "tag-rename a b"
+        );
+        assert_eq!(parse_error("tag-merge a b").1, "a space");
+        assert_eq!(parse_error("tag-merge into c").1, "a tag");
+        assert_eq!(
+            parse_error("tag 3 #x").1,
+            "a tag change like +#tag or -#tag"
+        );
+        assert_eq!(parse_error("tag 3").1, "a space");
+    }
+
+    #[test]
+    fn test_lists() {
+        assert_eq!(
+            parse_line(1, "use work"),
+            Ok(Query::Use(String::from("work")))
+        );
+        for line in &[
+            "use home-2",
+            "lists",
+            "move 3 work",
+            "search --rank --all-lists #q3",
+        ] {
+            assert_eq!(parse_line(1, line).unwrap().to_string(), *line);
+        }
+        assert_eq!(parse_error("use #work").1, "a list name");
+        assert_eq!(parse_error("move 3").1, "a space");
+        assert_eq!(parse_error("lists work").1, "end of line");
+    }
+
+    #[test]
+    fn test_dependencies() {
+        match parse_line(1, "add \"deploy\" after:3 after:5 after:3") {
+            Ok(Query::Add(_, _, attrs)) => {
+                assert_eq!(attrs.after, vec![Index::new(3), Index::new(5)])
+            }
+            other => panic!("not an add: {:?}", other),
+        }
+        for line in &[
+            "add \"deploy\" #ops after:3 after:5",
+            "blocks 3 5",
+            "search is:ready #ops",
+            "search --all is:blocked or not is:ready",
+        ] {
+            assert_eq!(parse_line(1, line).unwrap().to_string(), *line);
+        }
+        // Without `is:` these are just words.
+        for word in &["ready", "blocked", "overdue"] {
+            match parse_line(1, &format!("search {}", word)) {
+                Ok(Query::Search(sp)) => {
+                    assert_eq!(sp.expr, SearchExpr::Word(SearchWord::new(word)))
+                }
+                other => panic!("not a search: {:?}", other),
+            }
+        }
+        assert_eq!(parse_error("search is:done").1, "overdue, ready or blocked");
+        assert_eq!(parse_error("blocks 3").1, "a space");
+        assert_eq!(parse_error("add \"x\" after:").1, "an index");
+    }
+
+    #[test]
+    fn test_clearing_attributes() {
+        let attrs = |line: &str| match parse_line(1, line) {
+            Ok(Query::Edit(_, Edit::All(_, _, attrs))) => attrs,
+            other => panic!("not an edit: {:?}", other),
+        };
+        let line = "edit 2 \"x\" !none due:none every:none parent:none after:none";
+        assert_eq!(
+            attrs(line).cleared,
+            vec![
+                AttributeKind::Priority,
+                AttributeKind::Due,
+                AttributeKind::Recurrence,
+                AttributeKind::Parent,
+                AttributeKind::After,
+            ]
+        );
+        assert_eq!(parse_line(1, line).unwrap().to_string(), line);
+
+        // The last one given counts.
+        let a = attrs("edit 2 \"x\" due:none due:2026-11-01 !high !none");
+        assert_eq!(a.due, Some(DateExpr::On("2026-11-01".parse().unwrap())));
+        assert_eq!(
+            (a.priority, a.cleared),
+            (None, vec![AttributeKind::Priority])
+        );
+        let a = attrs("edit 2 \"x\" after:3 after:none after:4");
+        assert_eq!(
+            (a.after, a.cleared),
+            (vec![Index::new(4)], vec![AttributeKind::After])
+        );
+        assert_eq!(parse_error("edit 2 \"x\" due:never").1, "a date");
+        assert_eq!(parse_error("edit 2 \"x\" !nothing").1, "a priority");
+    }
+
+    #[test]
+    fn test_recurrence() {
+        let every = |line: &str| match parse_line(1, line) {
+            Ok(Query::Add(_, _, attrs)) => attrs.recurrence.unwrap(),
+            other => panic!("not an add: {:?}", other),
+        };
+        assert_eq!(every("add \"x\" every:week"), Recurrence::Weeks(1));
+        assert_eq!(every("add \"x\" every:2d"), Recurrence::Days(2));
+        assert_eq!(every("add \"x\" every:3w"), Recurrence::Weeks(3));
+        assert_eq!(every("add \"x\" every:month"), Recurrence::Monthly);
+        assert_eq!(
+            every("add \"x\" every:month-on-1"),
+            Recurrence::MonthlyOn(1)
+        );
+        for line in &[
+            "add \"bins\" #home due:2026-10-16 every:week",
+            "add \"x\" every:day",
+            "add \"x\" every:2w",
+            "add \"x\" every:month-on-31",
+        ] {
+            assert_eq!(parse_line(1, line).unwrap().to_string(), *line);
+        }
+        for line in &[
+            "add \"x\" every:0d",
+            "add \"x\" every:month-on-32",
+            "add \"x\" every:fortnight",
+        ] {
+            assert_eq!(parse_error(line).1, "a recurrence");
+        }
+        // The journal keeps the day a monthly series is anchored on.
+        assert_eq!(
+            parse_line(1, "add \"x\" due:+2w every:month")
+                .unwrap()
+                .resolve("2027-01-17".parse().unwrap())
+                .unwrap()
+                .to_string(),
+            "add \"x\" due:2027-01-31 every:month-on-31"
+        );
+    }
+
+    #[test]
+    fn test_relative_dates() {
+        let due = |line: &str| match parse_line(1, line) {
+            Ok(Query::Add(_, _, attrs)) => attrs.due.unwrap(),
+            other => panic!("not an add: {:?}", other),
+        };
+        assert_eq!(due("add \"x\" due:tomorrow"), DateExpr::Tomorrow);
+        assert_eq!(
+            due("add \"x\" due:next-friday"),
+            DateExpr::Next(Weekday::Friday)
+        );
+        assert_eq!(due("add \"x\" due:+3d"), DateExpr::InDays(3));
+        assert_eq!(due("add \"x\" due:+2w"), DateExpr::InWeeks(2));
+        assert_eq!(due("add \"x\" due:eom"), DateExpr::EndOfMonth);
+
+        for line in &[
+            "search due<=next-sunday",
+            "search due>+10d",
+            "search due:eom",
+            "add \"x\" due:+2w",
+        ] {
+            let q = parse_line(1, line).unwrap().to_string();
+            assert_eq!(q.replace("due=", "due:"), *line);
+        }
+        for line in &[
+            "add \"x\" due:+3",
+            "add \"x\" due:+3days",
+            "add \"x\" due:next-fri",
+        ] {
+            assert_eq!(parse_error(line).1, "a date");
+        }
+        assert_eq!(
+            parse_line(1, "add \"x\" due:next-friday")
+                .unwrap()
+                .resolve("2026-10-15".parse().unwrap())
+                .unwrap()
+                .to_string(),
+            "add \"x\" due:2026-10-16"
+        );
+    }
+
+    #[test]
+    fn test_parse_query_count() {
+        assert_eq!(parse_query_count(1, "10"), Ok(10));
+        assert_eq!(parse_query_count(1, " 3 "), Ok(3));
+        let e = parse_query_count(1, "add \"x\"").unwrap_err();
+        assert_eq!(
+            (e.column, e.expected.as_str(), e.found.as_str()),
+            (1, "a query count", "add")
+        );
+    }
+
+    #[test]
+    fn test_parse_line_accepts_trailing_whitespace() {
+        assert_eq!(parse_line(1, "done 3  "), Ok(Query::Done(Index::new(3))));
+        assert_eq!(parse_line(1, "undone 3"), Ok(Query::Undone(Index::new(3))));
+        assert_eq!(parse_line(1, "delete 3"), Ok(Query::Delete(Index::new(3))));
+    }
+
+    #[test]
+    fn test_search_expressions() {
+        let expr = |line: &str| match parse_line(1, line) {
+            Ok(Query::Search(sp)) => sp.expr,
+            other => panic!("not a search: {:?}", other),
+        };
+        let word = |w: &str| SearchExpr::Word(SearchWord::new(w));
+        let not_tag = |t: &str| SearchExpr::Not(Box::new(SearchExpr::Tag(Tag::new(t))));
+
+        assert_eq!(
+            expr("search buy or call not #work"),
+            SearchExpr::And(vec![
+                SearchExpr::Or(vec![word("buy"), word("call")]),
+                not_tag("work"),
+            ])
+        );
+        assert_eq!(
+            expr("search (buy and milk) or -#done-later"),
+            SearchExpr::Or(vec![
+                SearchExpr::And(vec![word("buy"), word("milk")]),
+                not_tag("done-later"),
+            ])
+        );
+        assert_eq!(expr("search"), SearchExpr::And(vec![]));
+        assert_eq!(
+            parse_line(1, "search buy or call not #work")
+                .unwrap()
+                .to_string(),
+            "search (buy or call) not #work"
+        );
+
+        let e = parse_line(1, "search buy or )").unwrap_err();
+        assert_eq!((e.column, e.expected.as_str()), (15, "a search term"));
+        let e = parse_line(1, "search (buy").unwrap_err();
+        assert_eq!(
+            (e.column, e.expected.as_str()),
+            (12, "a closing parenthesis")
+        );
+
+        // Nesting is limited instead of overflowing the stack.
+        let nested = |n: usize, open: &str, close: &str| {
+            format!("search {}buy{}", open.repeat(n), close.repeat(n))
+        };
+        assert!(parse_line(1, &nested(MAX_SEARCH_DEPTH, "(", ")")).is_ok());
+        assert!(parse_line(1, &nested(MAX_SEARCH_DEPTH, "not ", "")).is_ok());
+        for line in &[
+            nested(MAX_SEARCH_DEPTH + 1, "(", ")"),
+            nested(2000, "(", ")"),
+            nested(20000, "not ", ""),
+            nested(20000, "(not ", ")"),
+        ] {
+            assert_eq!(parse_error(line).1, "a search that nests less deeply");
+        }
+    }
+
+    #[test]
+    fn test_search_flags() {
+        let params = |line: &str| match parse_line(1, line) {
+            Ok(Query::Search(sp)) => sp,
+            other => panic!("not a search: {:?}", other),
+        };
+        assert_eq!(params("search buy").mode, MatchMode::Subsequence);
+        assert_eq!(params("search --prefix buy").mode, MatchMode::Prefix);
+        assert_eq!(params("search --exact").mode, MatchMode::Exact);
+        assert_eq!(
+            params("search --prefix --substring buy").mode,
+            MatchMode::Substring
+        );
+        assert_eq!(
+            parse_line(1, "search --exact buy or call")
+                .unwrap()
+                .to_string(),
+            "search --exact buy or call"
+        );
+        assert_eq!(params("search buy").status, StatusFilter::Open);
+        assert_eq!(params("search --all buy").status, StatusFilter::All);
+        assert_eq!(
+            params("search --all --done-only").status,
+            StatusFilter::Done
+        );
+        assert_eq!(
+            parse_line(1, "search --done-only --prefix buy")
+                .unwrap()
+                .to_string(),
+            "search --prefix --done-only buy"
+        );
+
+        let e = parse_line(1, "search --fuzzy buy").unwrap_err();
+        assert_eq!(
+            (e.column, e.expected.as_str(), e.found.as_str()),
+            (10, "a search flag", "fuzzy")
+        );
+    }
+
+    #[test]
+    fn test_edit() {
+        for line in &[
+            "edit 3 \"buy oat milk\" #groceries #dairy",
+            "edit 3 \"buy oat milk\"",
+            "edit-description 3 \"buy oat milk\"",
+            "edit-tags 3 #groceries",
+            "edit-tags 3",
+            "edit 3 \"fix login\" #bug !high",
+            "edit-priority 3 low",
+            "edit-priority 3 none",
+        ] {
+            assert_eq!(parse_line(1, line).unwrap().to_string(), *line);
+        }
+        assert_eq!(
+            parse_line(1, "edit-tags 0 #a"),
+            Ok(Query::Edit(
+                Index::new(0),
+                Edit::Tags(Tag::from_strings(vec!["a"]))
+            ))
+        );
+
+        let e = parse_line(1, "edit-description 3 #a").unwrap_err();
+        assert_eq!(
+            (e.column, e.expected.as_str()),
+            (20, "a quoted description")
+        );
+    }
 }
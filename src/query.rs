@@ -4,15 +4,435 @@ use crate::*;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Query {
-    Add(Description, Vec<Tag>),
+    Add(Description, Vec<Tag>, Attributes),
     Done(Index),
+    Undone(Index),
+    Delete(Index),
+    Edit(Index, Edit),
     Search(SearchParams),
+    /// Lists the items with their subtasks nested below them.
+    Tree(StatusFilter),
+    /// The first item has to be done before the second.
+    Block(Index, Index),
+    /// Switches to the named list.
+    Use(String),
+    Lists,
+    /// Moves an item of the list in use to the named list.
+    Move(Index, String),
+    TagTree,
+    /// Lists the tags in use with their counts.
+    Tags,
+    RenameTag(Tag, Tag),
+    /// Replaces all of the tags with the last one.
+    MergeTags(Vec<Tag>, Tag),
+    /// Adds and removes tags of one item.
+    Retag(Index, Vec<TagChange>),
+}
+
+/// One step of a `tag` query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagChange {
+    Add(Tag),
+    Remove(Tag),
+}
+
+impl fmt::Display for TagChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TagChange::Add(t) => write!(f, "+#{}", t),
+            TagChange::Remove(t) => write!(f, "-#{}", t),
+        }
+    }
+}
+
+/// What an `edit` query replaces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    Description(Description),
+    Tags(Vec<Tag>),
+    Priority(Option<Priority>),
+    All(Description, Vec<Tag>, Attributes),
+}
+
+impl Query {
+    /// The query with every relative date fixed to a day, so that it means
+    /// the same when the journal is replayed later. Fails if a day falls
+    /// outside what the journal can hold.
+    pub fn resolve(self, today: Date) -> Result<Query, QueryError> {
+        Ok(match self {
+            Query::Add(d, ts, attrs) => Query::Add(d, ts, attrs.resolve(today)?),
+            Query::Edit(i, Edit::All(d, ts, attrs)) => {
+                Query::Edit(i, Edit::All(d, ts, attrs.resolve(today)?))
+            }
+            q => q,
+        })
+    }
+
+    /// Whether running the query changes the list, i.e. whether it has to be journaled.
+    pub fn is_mutation(&self) -> bool {
+        match self {
+            Query::Add(_, _, _)
+            | Query::Done(_)
+            | Query::Undone(_)
+            | Query::Delete(_)
+            | Query::Edit(_, _)
+            | Query::Block(_, _)
+            | Query::Use(_)
+            | Query::Move(_, _)
+            | Query::RenameTag(_, _)
+            | Query::MergeTags(_, _)
+            | Query::Retag(_, _) => true,
+            Query::Search(_) | Query::Tree(_) | Query::Lists | Query::TagTree | Query::Tags => {
+                false
+            }
+        }
+    }
+}
+
+fn write_tags(f: &mut fmt::Formatter, tags: &[Tag]) -> fmt::Result {
+    for t in tags {
+        write!(f, " #{}", t)?;
+    }
+    Ok(())
+}
+
+fn write_attributes(f: &mut fmt::Formatter, attributes: &Attributes) -> fmt::Result {
+    for w in attributes.to_words() {
+        write!(f, " {}", w)?;
+    }
+    Ok(())
+}
+
+fn write_status(f: &mut fmt::Formatter, status: StatusFilter) -> fmt::Result {
+    match status {
+        StatusFilter::Open => Ok(()),
+        StatusFilter::All => write!(f, " --all"),
+        StatusFilter::Done => write!(f, " --done-only"),
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Query::Add(desc, tags, attrs) => {
+                write!(f, "add {}", desc.quoted())?;
+                write_tags(f, tags)?;
+                write_attributes(f, attrs)
+            }
+            Query::Done(idx) => write!(f, "done {}", idx),
+            Query::Undone(idx) => write!(f, "undone {}", idx),
+            Query::Delete(idx) => write!(f, "delete {}", idx),
+            Query::Edit(idx, Edit::Description(desc)) => {
+                write!(f, "edit-description {} {}", idx, desc.quoted())
+            }
+            Query::Edit(idx, Edit::Tags(tags)) => {
+                write!(f, "edit-tags {}", idx)?;
+                write_tags(f, tags)
+            }
+            Query::Edit(idx, Edit::Priority(p)) => match p {
+                Some(p) => write!(f, "edit-priority {} {}", idx, p),
+                None => write!(f, "edit-priority {} none", idx),
+            },
+            Query::Edit(idx, Edit::All(desc, tags, attrs)) => {
+                write!(f, "edit {} {}", idx, desc.quoted())?;
+                write_tags(f, tags)?;
+                write_attributes(f, attrs)
+            }
+            Query::Search(params) => {
+                write!(f, "search")?;
+                match params.mode {
+                    MatchMode::Subsequence => {}
+                    MatchMode::Prefix => write!(f, " --prefix")?,
+                    MatchMode::Substring => write!(f, " --substring")?,
+                    MatchMode::Exact => write!(f, " --exact")?,
+                }
+                write_status(f, params.status)?;
+                if params.rank {
+                    write!(f, " --rank")?;
+                }
+                if params.by_priority {
+                    write!(f, " --by-priority")?;
+                }
+                if params.all_lists {
+                    write!(f, " --all-lists")?;
+                }
+                match &params.expr {
+                    SearchExpr::And(es) if es.is_empty() => Ok(()),
+                    expr => write!(f, " {}", expr),
+                }
+            }
+            Query::Tree(status) => {
+                write!(f, "tree")?;
+                write_status(f, *status)
+            }
+            Query::Block(blocker, blocked) => write!(f, "blocks {} {}", blocker, blocked),
+            Query::Use(name) => write!(f, "use {}", name),
+            Query::Lists => write!(f, "lists"),
+            Query::Move(idx, name) => write!(f, "move {} {}", idx, name),
+            Query::TagTree => write!(f, "tag-tree"),
+            Query::Tags => write!(f, "tags"),
+            Query::RenameTag(from, to) => write!(f, "tag-rename {} {}", from, to),
+            Query::MergeTags(from, to) => {
+                write!(f, "tag-merge")?;
+                for t in from {
+                    write!(f, " {}", t)?;
+                }
+                write!(f, " into {}", to)
+            }
+            Query::Retag(idx, changes) => {
+                write!(f, "tag {}", idx)?;
+                for c in changes {
+                    write!(f, " {}", c)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SearchParams {
-    pub words: Vec<SearchWord>,
-    pub tags: Vec<Tag>,
+    pub expr: SearchExpr,
+    pub mode: MatchMode,
+    pub status: StatusFilter,
+    /// Order results by relevance instead of recency.
+    pub rank: bool,
+    /// Order results by priority first, highest first.
+    pub by_priority: bool,
+    /// Search every list of the workspace instead of the one in use.
+    pub all_lists: bool,
+}
+
+/// Which items a search looks at, by whether they are done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StatusFilter {
+    #[default]
+    Open,
+    All,
+    Done,
+}
+
+impl StatusFilter {
+    pub fn admits(self, done: bool) -> bool {
+        match self {
+            StatusFilter::Open => !done,
+            StatusFilter::All => true,
+            StatusFilter::Done => done,
+        }
+    }
+}
+
+/// How a search word or tag has to match a word of the item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchMode {
+    /// The letters appear in order, not necessarily next to each other.
+    #[default]
+    Subsequence,
+    /// The word starts with the pattern.
+    Prefix,
+    /// The pattern appears somewhere in the word.
+    Substring,
+    /// The word is the pattern.
+    Exact,
+}
+
+/// What a search is looking for. Words and tags match according to
+/// `SearchParams::mode`; an empty `And` matches every item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchExpr {
+    Word(SearchWord),
+    Tag(Tag),
+    Priority(Comparison, Priority),
+    Date(DateField, Comparison, DateExpr),
+    DateWithin(DateField, Period),
+    /// Open and due before today.
+    Overdue,
+    /// Every item it waits for is done.
+    Ready,
+    /// It waits for an open item.
+    Blocked,
+    Not(Box<SearchExpr>),
+    And(Vec<SearchExpr>),
+    Or(Vec<SearchExpr>),
+}
+
+impl SearchExpr {
+    /// `first` and all of `others`, without nesting a lone operand.
+    pub fn and(first: SearchExpr, others: Vec<SearchExpr>) -> SearchExpr {
+        if others.is_empty() {
+            first
+        } else {
+            let mut es = vec![first];
+            es.extend(others);
+            SearchExpr::And(es)
+        }
+    }
+
+    /// `first` or any of `others`, without nesting a lone operand.
+    pub fn or(first: SearchExpr, others: Vec<SearchExpr>) -> SearchExpr {
+        if others.is_empty() {
+            first
+        } else {
+            let mut es = vec![first];
+            es.extend(others);
+            SearchExpr::Or(es)
+        }
+    }
+}
+
+impl fmt::Display for SearchExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operands = |es: &[SearchExpr], sep: &str| -> String {
+            es.iter()
+                .map(|e| match e {
+                    SearchExpr::And(_) | SearchExpr::Or(_) => format!("({})", e),
+                    _ => e.to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(sep)
+        };
+        match self {
+            SearchExpr::Word(w) => write!(f, "{}", w.value()),
+            SearchExpr::Tag(t) => write!(f, "#{}", t),
+            SearchExpr::Priority(cmp, p) => write!(f, "priority{}{}", cmp, p),
+            SearchExpr::Date(field, cmp, d) => write!(f, "{}{}{}", field, cmp, d),
+            SearchExpr::DateWithin(field, p) => write!(f, "{}:{}", field, p),
+            SearchExpr::Overdue => write!(f, "is:overdue"),
+            SearchExpr::Ready => write!(f, "is:ready"),
+            SearchExpr::Blocked => write!(f, "is:blocked"),
+            SearchExpr::Not(e) => match e.as_ref() {
+                SearchExpr::And(_) | SearchExpr::Or(_) => write!(f, "not ({})", e),
+                _ => write!(f, "not {}", e),
+            },
+            SearchExpr::And(es) => write!(f, "{}", operands(es, " ")),
+            SearchExpr::Or(es) => write!(f, "{}", operands(es, " or ")),
+        }
+    }
+}
+
+/// A date as written in a query, which may depend on the day it is run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateExpr {
+    On(Date),
+    Today,
+    Tomorrow,
+    /// The first such weekday after today, so at most a week away.
+    Next(Weekday),
+    InDays(u32),
+    InWeeks(u32),
+    /// The last day of the current month.
+    EndOfMonth,
+}
+
+impl DateExpr {
+    pub fn resolve(self, today: Date) -> Date {
+        match self {
+            DateExpr::On(d) => d,
+            DateExpr::Today => today,
+            DateExpr::Tomorrow => today.add_days(1),
+            DateExpr::Next(w) => {
+                let ahead = (w.number() - today.weekday().number()).rem_euclid(7);
+                today.add_days(if ahead == 0 { 7 } else { ahead })
+            }
+            DateExpr::InDays(n) => today.add_days(i64::from(n)),
+            DateExpr::InWeeks(n) => today.add_days(7 * i64::from(n)),
+            DateExpr::EndOfMonth => today.end_of_month(),
+        }
+    }
+}
+
+impl fmt::Display for DateExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DateExpr::On(d) => write!(f, "{}", d),
+            DateExpr::Today => write!(f, "today"),
+            DateExpr::Tomorrow => write!(f, "tomorrow"),
+            DateExpr::Next(w) => write!(f, "next-{}", w),
+            DateExpr::InDays(n) => write!(f, "+{}d", n),
+            DateExpr::InWeeks(n) => write!(f, "+{}w", n),
+            DateExpr::EndOfMonth => write!(f, "eom"),
+        }
+    }
+}
+
+/// The dates of an item that searches can look at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateField {
+    Due,
+    Created,
+    Completed,
+}
+
+impl fmt::Display for DateField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DateField::Due => write!(f, "due"),
+            DateField::Created => write!(f, "created"),
+            DateField::Completed => write!(f, "completed"),
+        }
+    }
+}
+
+/// A span of days around today.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    /// Monday to Sunday.
+    ThisWeek,
+}
+
+impl Period {
+    /// The first and last day of the period.
+    pub fn range(self, today: Date) -> (Date, Date) {
+        match self {
+            Period::ThisWeek => {
+                let monday = today.add_days(-today.weekday().number());
+                (monday, monday.add_days(6))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Period::ThisWeek => write!(f, "this-week"),
+        }
+    }
+}
+
+/// How a value of an item is compared to the one in a search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    /// Whether `value` compares to `bound` this way.
+    pub fn holds<T: Ord>(self, value: T, bound: T) -> bool {
+        match self {
+            Comparison::Less => value < bound,
+            Comparison::LessOrEqual => value <= bound,
+            Comparison::Equal => value == bound,
+            Comparison::GreaterOrEqual => value >= bound,
+            Comparison::Greater => value > bound,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Comparison::Less => write!(f, "<"),
+            Comparison::LessOrEqual => write!(f, "<="),
+            Comparison::Equal => write!(f, "="),
+            Comparison::GreaterOrEqual => write!(f, ">="),
+            Comparison::Greater => write!(f, ">"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -26,39 +446,398 @@ impl SearchWord {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    /// The list the item is in, for searches across lists.
+    pub list: Option<String>,
+    pub index: Index,
+    pub description: Description,
+    pub tags: Vec<Tag>,
+    pub done: bool,
+    pub attributes: Attributes,
+    /// The parents of the item, outermost first.
+    pub parents: Vec<Index>,
+    /// Relevance of the match, only computed for ranked searches.
+    pub score: Option<u32>,
+    /// Char positions in the description that matched the search.
+    pub description_matches: Vec<usize>,
+    /// Char positions that matched the search, per tag.
+    pub tag_matches: Vec<Vec<usize>>,
+}
+
+/// How matched characters are marked when printing search results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    Off,
+    /// Bold yellow, for terminals.
+    Ansi,
+    /// `[ma]tch`, for everything else.
+    Brackets,
+}
+
+impl SearchResult {
+    pub fn from_item(item: &TodoItem) -> SearchResult {
+        SearchResult {
+            list: None,
+            index: item.index,
+            description: item.description(),
+            tags: item.tags(),
+            done: item.done,
+            attributes: item.attributes(),
+            parents: item.parent.into_iter().collect(),
+            score: None,
+            description_matches: vec![],
+            tag_matches: vec![],
+        }
+    }
+
+    pub fn render(&self, highlight: Highlight) -> String {
+        let no_matches: Vec<usize> = vec![];
+        let tag_matches = |i: usize| -> &[usize] { self.tag_matches.get(i).unwrap_or(&no_matches) };
+
+        let mut line = match &self.list {
+            Some(name) => format!("{}:{}", name, self.index),
+            None => format!("{}", self.index),
+        };
+        if self.done {
+            line.push_str(" (done)");
+        }
+        line.push_str(&format!(
+            " \"{}\"",
+            mark(
+                self.description.value(),
+                &self.description_matches,
+                highlight
+            )
+        ));
+        for (i, t) in self.tags.iter().enumerate() {
+            line.push_str(&format!(" #{}", mark(t.value(), tag_matches(i), highlight)));
+        }
+        let attributes = Attributes {
+            parent: None,
+            ..self.attributes.clone()
+        };
+        for w in attributes.to_words() {
+            line.push_str(&format!(" {}", w));
+        }
+        if !self.parents.is_empty() {
+            let chain: Vec<String> = self.parents.iter().map(Index::to_string).collect();
+            line.push_str(&format!(" under {}", chain.join(" > ")));
+        }
+        line
+    }
+}
+
+/// Wraps each run of the chars of `text` at `positions` in highlight markers,
+/// escaping quotes and backslashes as in a query.
+fn mark(text: &str, positions: &[usize], highlight: Highlight) -> String {
+    let (open, close) = match highlight {
+        Highlight::Off => ("", ""),
+        Highlight::Ansi => ("\x1b[1;33m", "\x1b[0m"),
+        Highlight::Brackets => ("[", "]"),
+    };
+    let mut buff = String::new();
+    let mut in_run = false;
+    for (i, c) in text.chars().enumerate() {
+        let hit = positions.contains(&i);
+        if hit && !in_run {
+            buff.push_str(open);
+        } else if !hit && in_run {
+            buff.push_str(close);
+        }
+        in_run = hit;
+        push_escaped(&mut buff, c);
+    }
+    if in_run {
+        buff.push_str(close);
+    }
+    buff
+}
+
+impl fmt::Display for SearchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(Highlight::Off))
+    }
+}
+
+/// One line of the answer to `tags`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagSummary {
+    pub tag: Tag,
+    pub open: usize,
+    pub done: usize,
+}
+
+impl fmt::Display for TagSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}: {} open, {} done", self.tag, self.open, self.done)
+    }
+}
+
+/// One line of the answer to `lists`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListSummary {
+    pub name: String,
+    pub open: usize,
+    pub done: usize,
+    /// Whether this is the list in use.
+    pub current: bool,
+}
+
+impl fmt::Display for ListSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} open, {} done", self.name, self.open, self.done)?;
+        if self.current {
+            write!(f, " (in use)")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum QueryResult {
     Added(Index),
-    Done,
-    Found(Vec<Index>),
+    /// With the index of the next occurrence, if the item recurs.
+    Done(Option<Index>),
+    Undone,
+    Deleted,
+    Edited,
+    Found(Vec<SearchResult>),
+    /// Items with how deeply they are nested.
+    Tree(Vec<(usize, SearchResult)>),
+    Using(String),
+    Lists(Vec<ListSummary>),
+    /// The list an item was moved to, and its index there.
+    Moved(String, Index),
+    TagTree(Vec<TagNode>),
+    Tags(Vec<TagSummary>),
+    /// How many items a tag rename or merge changed.
+    Retagged(usize),
 }
 
-impl fmt::Display for QueryResult {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl QueryResult {
+    pub fn render(&self, highlight: Highlight) -> String {
         match &self {
-            QueryResult::Added(ti) => write!(f, "{}", ti),
-            QueryResult::Done => write!(f, "done"),
+            QueryResult::Added(ti) => format!("{}", ti),
+            QueryResult::Done(None) => String::from("done"),
+            QueryResult::Done(Some(next)) => format!("done, next is {}", next),
+            QueryResult::Undone => String::from("undone"),
+            QueryResult::Deleted => String::from("deleted"),
+            QueryResult::Edited => String::from("edited"),
             QueryResult::Found(rs) => {
                 let mut buff: Vec<String> = vec![];
                 buff.push(format!("{} item(s) found", rs.len()));
-                for i in rs {
-                    buff.push(format!("{}", i));
+                for r in rs {
+                    buff.push(r.render(highlight));
                 }
-                write!(f, "{}", buff.join("\n"))
+                buff.join("\n")
+            }
+            QueryResult::Using(name) => format!("using {}", name),
+            QueryResult::Lists(ls) => {
+                let mut buff: Vec<String> = vec![];
+                buff.push(format!("{} list(s)", ls.len()));
+                for l in ls {
+                    buff.push(l.to_string());
+                }
+                buff.join("\n")
+            }
+            QueryResult::Moved(name, idx) => format!("moved to {}:{}", name, idx),
+            QueryResult::Tags(ts) => {
+                let mut buff: Vec<String> = vec![];
+                buff.push(format!("{} tag(s)", ts.len()));
+                for t in ts {
+                    buff.push(t.to_string());
+                }
+                buff.join("\n")
+            }
+            QueryResult::Retagged(n) => format!("retagged {} item(s)", n),
+            QueryResult::TagTree(nodes) => {
+                fn lines(nodes: &[TagNode], depth: usize, buff: &mut Vec<String>) {
+                    for n in nodes {
+                        let indent = "  ".repeat(depth);
+                        buff.push(format!("{}{} ({})", indent, n.tag.name(), n.items));
+                        lines(&n.children, depth + 1, buff);
+                    }
+                }
+                let mut buff: Vec<String> = vec![];
+                lines(nodes, 0, &mut buff);
+                buff.insert(0, format!("{} tag(s)", buff.len()));
+                buff.join("\n")
+            }
+            QueryResult::Tree(rs) => {
+                let mut buff: Vec<String> = vec![];
+                buff.push(format!("{} item(s)", rs.len()));
+                for (depth, r) in rs {
+                    buff.push(format!("{}{}", "  ".repeat(*depth), r.render(highlight)));
+                }
+                buff.join("\n")
             }
         }
     }
 }
 
+impl fmt::Display for QueryResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(Highlight::Off))
+    }
+}
+
+/// Why an input line is not a valid query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub expected: String,
+    pub found: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: expected {}, found {:?}",
+            self.line, self.column, self.expected, self.found
+        )
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct QueryError(pub String);
+pub enum QueryError {
+    /// The line could not be parsed as a query.
+    Parse(ParseError),
+    /// No item has this index.
+    InvalidIndex(Index),
+    /// The item with this index was deleted.
+    Deleted(Index),
+    /// `done` on an item that is already done.
+    AlreadyDone(Index),
+    /// `undone` on an item that is still open.
+    NotDone(Index),
+    /// `done` on an item with open subtasks.
+    OpenSubtasks(Index),
+    /// Moving an item under itself or one of its subtasks.
+    ParentCycle(Index),
+    /// Making an item wait for itself, directly or not.
+    DependencyCycle(Index),
+    /// There is no list with this name.
+    UnknownList(String),
+    /// No item has this tag.
+    UnknownTag(Tag),
+    /// Removing a tag from an item that does not have it.
+    NotTagged(Index, Tag),
+    /// A due date past what `YYYY-MM-DD` can write.
+    DateOutOfRange(Date),
+    /// The query ran but could not be written to the journal.
+    Journal(String),
+}
 
 impl fmt::Display for QueryError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            QueryError::Parse(e) => return write!(f, "Could not parse the query at {}.", e),
+            QueryError::InvalidIndex(_) => String::from("Invalid Index"),
+            QueryError::Deleted(i) => format!("item {} was deleted", i),
+            QueryError::AlreadyDone(i) => format!("item {} is already done", i),
+            QueryError::NotDone(i) => format!("item {} is not done", i),
+            QueryError::OpenSubtasks(i) => format!("item {} has open subtasks", i),
+            QueryError::ParentCycle(i) => format!("item {} can't be its own subtask", i),
+            QueryError::DependencyCycle(i) => format!("item {} would wait for itself", i),
+            QueryError::UnknownList(name) => format!("there is no list called {:?}", name),
+            QueryError::UnknownTag(t) => format!("no item is tagged #{}", t),
+            QueryError::NotTagged(i, t) => format!("item {} is not tagged #{}", i, t),
+            QueryError::DateOutOfRange(d) => {
+                format!("{} is not between {} and {}", d, Date::MIN, Date::MAX)
+            }
+            QueryError::Journal(e) => format!("could not write to the journal: {}", e),
+        };
         write!(
             f,
             "An error occurred while processing the query: {}.",
-            self.0
+            reason
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_highlights() {
+        let r = SearchResult {
+            list: None,
+            index: Index::new(1),
+            description: Description::new("buy milk"),
+            tags: Tag::from_strings(vec!["groceries"]),
+            done: false,
+            attributes: Attributes::default(),
+            parents: vec![],
+            score: None,
+            description_matches: vec![4, 5, 7],
+            tag_matches: vec![vec![0]],
+        };
+        assert_eq!(r.render(Highlight::Off), "1 \"buy milk\" #groceries");
+        assert_eq!(
+            r.render(Highlight::Brackets),
+            "1 \"buy [mi]l[k]\" #[g]roceries"
+        );
+        assert_eq!(
+            r.render(Highlight::Ansi),
+            "1 \"buy \x1b[1;33mmi\x1b[0ml\x1b[1;33mk\x1b[0m\" #\x1b[1;33mg\x1b[0mroceries"
+        );
+    }
+
+    #[test]
+    fn test_resolve_relative_dates() {
+        let date = |s: &str| -> Date { s.parse().unwrap() };
+        // a Thursday
+        let today = date("2026-10-15");
+        for (expr, expected) in &[
+            (DateExpr::On(date("2026-11-01")), "2026-11-01"),
+            (DateExpr::Today, "2026-10-15"),
+            (DateExpr::Tomorrow, "2026-10-16"),
+            (DateExpr::Next(Weekday::Friday), "2026-10-16"),
+            (DateExpr::Next(Weekday::Thursday), "2026-10-22"),
+            (DateExpr::Next(Weekday::Monday), "2026-10-19"),
+            (DateExpr::InDays(3), "2026-10-18"),
+            (DateExpr::InDays(20), "2026-11-04"),
+            (DateExpr::InWeeks(2), "2026-10-29"),
+            (DateExpr::EndOfMonth, "2026-10-31"),
+        ] {
+            assert_eq!(expr.resolve(today), date(expected), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_render_marks_done_items() {
+        let mut r = SearchResult {
+            list: None,
+            index: Index::new(1),
+            description: Description::new("buy milk"),
+            tags: vec![],
+            done: true,
+            attributes: Attributes {
+                priority: Some(Priority::High),
+                due: Date::new(2026, 11, 1).map(DateExpr::On),
+                recurrence: Some(Recurrence::Weeks(1)),
+                parent: Some(Index::new(12)),
+                after: vec![Index::new(4)],
+                cleared: vec![],
+            },
+            parents: vec![Index::new(3), Index::new(12)],
+            score: None,
+            description_matches: vec![4],
+            tag_matches: vec![],
+        };
+        assert_eq!(
+            r.render(Highlight::Brackets),
+            "1 (done) \"buy [m]ilk\" !high due:2026-11-01 every:week after:4 under 3 > 12"
+        );
+        r.done = false;
+        r.attributes = Attributes::default();
+        r.parents = vec![];
+        assert_eq!(r.to_string(), "1 \"buy milk\"");
+        r.description = Description::new("say \"hi\"");
+        assert_eq!(r.to_string(), "1 \"say \\\"hi\\\"\"");
+    }
+}
@@ -1,25 +1,100 @@
 use crate::*;
 
-pub fn run_line(line: &str, tl: &mut TodoList) {
-    if let Ok((_, q)) = parser::query(line) {
-        match run_query(q, tl) {
-            Ok(r) => {
-                println!("{}", r);
-            }
-            Err(e) => {
-                eprintln!("Error: {}", e);
-            }
+/// How `run_line` presents its results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    pub highlight: Highlight,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            highlight: Highlight::Off,
         }
     }
 }
 
-fn run_query(q: Query, tl: &mut TodoList) -> Result<QueryResult, QueryError> {
+/// Runs the query on line number `line_no` and prints its result, or the
+/// error on stderr. Blank lines are skipped.
+///
+/// The error is also returned, so that callers can tell whether any line failed.
+/// A query that ran but could not be journaled fails with `QueryError::Journal`
+/// and its result is not printed: the list in memory is then ahead of the
+/// journal, so callers should stop.
+pub fn run_line(
+    line_no: usize,
+    line: &str,
+    ws: &mut Workspace,
+    journal: Option<&mut Journal>,
+    opts: &Options,
+) -> Result<(), QueryError> {
+    if line.trim().is_empty() {
+        return Ok(());
+    }
+    // The whole query runs at one moment, the one it is journaled with, so
+    // that replaying it stamps the same times and due dates.
+    let at = ws.now();
+    let clock = ws.clock();
+    ws.set_clock(Clock::Fixed(at));
+    let result = parser::parse_line(line_no, line)
+        .map_err(QueryError::Parse)
+        .and_then(|q| {
+            let q = q.resolve(ws.today())?;
+            let entry = if q.is_mutation() {
+                Some(q.clone())
+            } else {
+                None
+            };
+            run_query(q, ws).map(|r| (entry, r))
+        });
+    ws.set_clock(clock);
+    let result = result.and_then(|(entry, r)| match (entry, journal) {
+        (Some(q), Some(j)) => j
+            .append(&q, at)
+            .map(|_| r)
+            .map_err(|e| QueryError::Journal(e.to_string())),
+        _ => Ok(r),
+    });
+    match result {
+        Ok(r) => {
+            println!("{}", r.render(opts.highlight));
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            Err(e)
+        }
+    }
+}
+
+/// Runs `q` against the list in use, or against the workspace for the
+/// queries about lists.
+pub fn run_query(q: Query, ws: &mut Workspace) -> Result<QueryResult, QueryError> {
+    let tl = ws.current_mut();
     match q {
-        Query::Add(desc, tags) => Ok(QueryResult::Added(tl.push(desc, tags))),
-        Query::Done(idx) => match tl.done_with_index(idx) {
-            None => Err(QueryError(String::from("Invalid Index"))),
-            Some(_) => Ok(QueryResult::Done),
-        },
-        Query::Search(params) => Ok(QueryResult::Found(tl.search(params))),
+        Query::Add(desc, tags, attrs) => tl.add(desc, tags, attrs).map(QueryResult::Added),
+        Query::Done(idx) => tl
+            .done_with_index(idx)
+            .and_then(|i| tl.get(i))
+            .map(|item| QueryResult::Done(item.next)),
+        Query::Undone(idx) => tl.undone_with_index(idx).map(|_| QueryResult::Undone),
+        Query::Delete(idx) => tl.delete(idx).map(|_| QueryResult::Deleted),
+        Query::Edit(idx, edit) => tl.edit(idx, edit).map(|_| QueryResult::Edited),
+        Query::Search(params) => Ok(QueryResult::Found(ws.search(params))),
+        Query::Tree(status) => Ok(QueryResult::Tree(tl.tree(status))),
+        Query::Block(blocker, blocked) => tl.block(blocker, blocked).map(|_| QueryResult::Edited),
+        Query::Use(name) => {
+            ws.use_list(&name);
+            Ok(QueryResult::Using(name))
+        }
+        Query::Lists => Ok(QueryResult::Lists(ws.summary())),
+        Query::TagTree => Ok(QueryResult::TagTree(tl.tag_tree())),
+        Query::Tags => Ok(QueryResult::Tags(tl.tag_summary())),
+        Query::RenameTag(from, to) => tl.rename_tags(&[from], &to).map(QueryResult::Retagged),
+        Query::MergeTags(from, to) => tl.rename_tags(&from, &to).map(QueryResult::Retagged),
+        Query::Retag(idx, changes) => tl.retag(idx, &changes).map(|_| QueryResult::Edited),
+        Query::Move(idx, name) => ws
+            .move_item(idx, &name)
+            .map(|i| QueryResult::Moved(name, i)),
     }
 }
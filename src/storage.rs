@@ -0,0 +1,743 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::*;
+
+const SNAPSHOT_HEADER: &str = "todo-snapshot 2";
+/// Snapshots from before workspaces, holding a single list.
+const SINGLE_LIST_SNAPSHOT_HEADER: &str = "todo-snapshot 1";
+const GENERATION_PREFIX: &str = "# generation ";
+
+/// The highest top index a snapshot may give a list, far beyond what any
+/// list reaches; restoring a list makes room for every index below it.
+const MAX_TOP_INDEX: u64 = 1 << 24;
+
+/// Append-only log of the mutating queries run against a `Workspace`.
+///
+/// Every entry is a single line in the query language, prefixed with the time
+/// it was run (`@2026-10-15T09:30:00Z done 3`), so replaying the journal
+/// through the runner rebuilds the list, `top_index` and timestamps included.
+///
+/// The journal can be compacted into a snapshot stored next to it (see
+/// `snapshot_path`). Both files carry a generation number: a journal is only
+/// replayed on top of the snapshot of the same generation, so a crash
+/// between writing a snapshot and resetting the journal never applies the
+/// same entries twice.
+#[derive(Debug)]
+pub struct Journal {
+    file: File,
+    path: PathBuf,
+    generation: u64,
+    dropped: Option<String>,
+}
+
+impl Journal {
+    /// Opens (or creates) the journal at `path` and loads the snapshot, then
+    /// the journal, into `ws`.
+    ///
+    /// A trailing line without a newline is what a crash in the middle of
+    /// `append` leaves behind; it is cut off instead of being replayed, and
+    /// kept for `dropped_entry`.
+    pub fn open(path: &Path, ws: &mut Workspace) -> io::Result<Journal> {
+        let snapshot_generation = match read_snapshot(&snapshot_path(path))? {
+            Some((generation, mut snapshot)) => {
+                snapshot.set_clock(ws.clock());
+                snapshot.set_subtasks_block_done(ws.subtasks_block_done());
+                *ws = snapshot;
+                generation
+            }
+            None => 0,
+        };
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        let complete = match contents.rfind('\n') {
+            Some(i) => i + 1,
+            None => 0,
+        };
+        let dropped = if complete < contents.len() {
+            file.set_len(complete as u64)?;
+            file.sync_all()?;
+            Some(contents[complete..].to_owned())
+        } else {
+            None
+        };
+
+        let (generation, entries) = split_generation(&contents[..complete])?;
+        let mut journal = Journal {
+            file,
+            path: path.to_owned(),
+            generation,
+            dropped,
+        };
+        if generation < snapshot_generation {
+            // Compaction stopped after the snapshot was in place: everything
+            // in this journal is already part of the snapshot.
+            journal.reset(snapshot_generation)?;
+        } else if generation > snapshot_generation {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "journal is at generation {} but the snapshot is at {}",
+                    generation, snapshot_generation
+                ),
+            ));
+        } else {
+            replay(entries, ws)?;
+        }
+        Ok(journal)
+    }
+
+    /// The truncated entry `open` cut off the end of the journal, if any.
+    pub fn dropped_entry(&self) -> Option<&str> {
+        self.dropped.as_deref()
+    }
+
+    /// Durably records `q`, run at `at`; returns only once the entry has hit
+    /// the disk.
+    pub fn append(&mut self, q: &Query, at: Timestamp) -> io::Result<()> {
+        self.file.write_all(format!("@{} {}\n", at, q).as_bytes())?;
+        self.file.sync_data()
+    }
+
+    /// Writes `ws` out as the new snapshot and empties the journal.
+    ///
+    /// `ws` has to be the workspace this journal was replayed into. A
+    /// snapshot that would not read back as `ws` is never written, so the
+    /// store is left as it was.
+    pub fn compact(&mut self, ws: &Workspace) -> io::Result<()> {
+        let generation = self.generation + 1;
+        let snapshot = render_snapshot(generation, ws);
+        if parse_snapshot(&snapshot)? != (generation, ws.clone()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the snapshot would not read back as the current lists",
+            ));
+        }
+        write_atomically(&snapshot_path(&self.path), &snapshot)?;
+        self.reset(generation)
+    }
+
+    fn reset(&mut self, generation: u64) -> io::Result<()> {
+        write_atomically(
+            &self.path,
+            &format!("{}{}\n", GENERATION_PREFIX, generation),
+        )?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.generation = generation;
+        Ok(())
+    }
+}
+
+/// Where the snapshot for the journal at `journal` lives.
+pub fn snapshot_path(journal: &Path) -> PathBuf {
+    let mut name = journal.as_os_str().to_owned();
+    name.push(".snapshot");
+    PathBuf::from(name)
+}
+
+fn split_generation(entries: &str) -> io::Result<(u64, &str)> {
+    if !entries.starts_with(GENERATION_PREFIX) {
+        return Ok((0, entries));
+    }
+    let end = entries.find('\n').unwrap_or(entries.len());
+    match entries[GENERATION_PREFIX.len()..end].parse::<u64>() {
+        Ok(generation) => Ok((generation, &entries[end..])),
+        Err(_) => Err(corrupt(1, &entries[..end], "bad generation header")),
+    }
+}
+
+fn replay(entries: &str, ws: &mut Workspace) -> io::Result<()> {
+    // Every entry succeeded when it was run, maybe under other rules.
+    let (clock, subtasks_block_done) = (ws.clock(), ws.subtasks_block_done());
+    ws.set_subtasks_block_done(false);
+    let result = replay_entries(entries, ws);
+    ws.set_clock(clock);
+    ws.set_subtasks_block_done(subtasks_block_done);
+    result
+}
+
+fn replay_entries(entries: &str, ws: &mut Workspace) -> io::Result<()> {
+    for (n, line) in entries.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        // Entries written before they carried a time are replayed as if
+        // they were run now.
+        let query = match line.strip_prefix('@') {
+            Some(entry) => {
+                let (at, query) = entry.split_once(' ').unwrap_or((entry, ""));
+                match at.parse::<Timestamp>() {
+                    Ok(at) => ws.set_clock(Clock::Fixed(at)),
+                    Err(e) => return Err(corrupt(n + 1, line, &e)),
+                }
+                query
+            }
+            None => line,
+        };
+        let q = match parser::parse_line(n + 1, query) {
+            Ok(q) if q.is_mutation() => q,
+            Ok(_) => return Err(corrupt(n + 1, line, "not a query that changes the list")),
+            Err(e) => return Err(corrupt(n + 1, line, &e.to_string())),
+        };
+        match runner::run_query(q, ws) {
+            // Journals from before `done` refused finished items may mark
+            // an item done twice.
+            Ok(_) | Err(QueryError::AlreadyDone(_)) => {}
+            Err(e) => return Err(corrupt(n + 1, line, &e.to_string())),
+        }
+    }
+    Ok(())
+}
+
+fn corrupt(line_no: usize, line: &str, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("journal line {}: {:?}: {}", line_no, line, reason),
+    )
+}
+
+/// Snapshot layout, with a section per list where deleted items are
+/// simply left out:
+///
+/// ```text
+/// todo-snapshot 2
+/// generation <n>
+/// current <list>
+/// list <name> <top-index>
+/// <index> <open|done> "<description>" #tag... !priority due:<date> every:<recurrence> \
+///     parent:<index> after:<index>... created:<time> completed:<time> series:<index> \
+///     next:<index>
+/// ```
+///
+/// Version 1 snapshots have `top-index <n>` instead of `current` and the
+/// list sections, and the items of a single list.
+fn render_snapshot(generation: u64, ws: &Workspace) -> String {
+    let mut buff: Vec<String> = vec![
+        String::from(SNAPSHOT_HEADER),
+        format!("generation {}", generation),
+        format!("current {}", ws.current_name()),
+    ];
+    for (name, tl) in ws.lists() {
+        buff.push(format!("list {} {}", name, tl.top_index()));
+        for item in tl.items() {
+            buff.push(render_item(item));
+        }
+    }
+    buff.push(String::new());
+    buff.join("\n")
+}
+
+fn render_item(item: &TodoItem) -> String {
+    let mut line = format!(
+        "{} {} {}",
+        item.index,
+        if item.done { "done" } else { "open" },
+        item.description().quoted()
+    );
+    for t in &item.tags {
+        line.push_str(&format!(" #{}", t));
+    }
+    for w in item.attributes().to_words() {
+        line.push_str(&format!(" {}", w));
+    }
+    if let Some(t) = item.created_at {
+        line.push_str(&format!(" created:{}", t));
+    }
+    if let Some(t) = item.completed_at {
+        line.push_str(&format!(" completed:{}", t));
+    }
+    if let Some(i) = item.series {
+        line.push_str(&format!(" series:{}", i));
+    }
+    if let Some(i) = item.next {
+        line.push_str(&format!(" next:{}", i));
+    }
+    line
+}
+
+fn read_snapshot(path: &Path) -> io::Result<Option<(u64, Workspace)>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => parse_snapshot(&contents).map(Some),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn parse_snapshot(contents: &str) -> io::Result<(u64, Workspace)> {
+    let bad = |line_no: usize, line: &str, reason: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("snapshot line {}: {:?}: {}", line_no, line, reason),
+        )
+    };
+
+    let mut lines = contents.lines().enumerate().map(|(n, l)| (n + 1, l));
+    let single_list = match lines.next() {
+        Some((_, SNAPSHOT_HEADER)) => false,
+        Some((_, SINGLE_LIST_SNAPSHOT_HEADER)) => true,
+        _ => return Err(bad(1, "", "not a todo snapshot")),
+    };
+    let mut field = |key: &str| {
+        let (n, line) = lines.next().unwrap_or((0, ""));
+        match line.strip_prefix(key) {
+            Some(value) => Ok(value),
+            None => Err(bad(n, line, &format!("expected {}", key.trim()))),
+        }
+    };
+    let generation = field("generation ")?;
+    let generation = generation
+        .parse::<u64>()
+        .map_err(|_| bad(2, generation, "expected a generation"))?;
+    let current = if single_list {
+        DEFAULT_LIST
+    } else {
+        field("current ")?
+    };
+
+    // The list sections, each with its top index and its items; indices
+    // missing between the items were deleted.
+    let mut sections: Vec<(String, u64, Vec<TodoItem>)> = vec![];
+    if single_list {
+        let top_index = field("top-index ")?;
+        let top_index = top_index
+            .parse::<u64>()
+            .map_err(|_| bad(3, top_index, "expected top-index"))?;
+        if top_index > MAX_TOP_INDEX {
+            return Err(bad(3, "", "top-index is too large"));
+        }
+        sections.push((String::from(DEFAULT_LIST), top_index, vec![]));
+    }
+    for (n, line) in lines {
+        if let Some(header) = line.strip_prefix("list ").filter(|_| !single_list) {
+            match header
+                .split_once(' ')
+                .map(|(name, top)| (name, top.parse::<u64>()))
+            {
+                Some((_, Ok(top_index))) if top_index > MAX_TOP_INDEX => {
+                    return Err(bad(n, line, "top index is too large"))
+                }
+                Some((name, Ok(top_index))) => {
+                    sections.push((name.to_owned(), top_index, vec![]));
+                    continue;
+                }
+                _ => return Err(bad(n, line, "bad list header")),
+            }
+        }
+        let (_, top_index, items) = match sections.last_mut() {
+            Some(section) => section,
+            None => return Err(bad(n, line, "item outside of a list")),
+        };
+        let next = items.last().map_or(0, |i| i.index.value() + 1);
+        match parser::snapshot_item(line) {
+            Ok(("", item)) if item.index.value() >= *top_index => {
+                return Err(bad(n, line, "index is not below the list's top index"))
+            }
+            Ok(("", item)) if item.index.value() >= next => items.push(item),
+            _ => return Err(bad(n, line, "bad item")),
+        }
+    }
+    for (name, _, items) in &sections {
+        check_links(name, items)?;
+    }
+    let lists = sections
+        .into_iter()
+        .map(|(name, top_index, items)| (name, TodoList::restore(Index::new(top_index), items)))
+        .collect();
+    Ok((generation, Workspace::restore(current, lists)))
+}
+
+/// Makes sure the parents and dependencies of `items`, sorted by index, are
+/// items of the same list, and that no item is its own ancestor.
+fn check_links(list: &str, items: &[TodoItem]) -> io::Result<()> {
+    let find = |i: Index| {
+        items
+            .binary_search_by_key(&i.value(), |item| item.index.value())
+            .ok()
+            .map(|pos| &items[pos])
+    };
+    let bad = |item: &TodoItem, reason: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("snapshot list {}: item {}: {}", list, item.index, reason),
+        )
+    };
+    for item in items {
+        if let Some(&dep) = item.after.iter().find(|&&dep| find(dep).is_none()) {
+            return Err(bad(item, &format!("waits for missing item {}", dep)));
+        }
+        // A parent chain longer than the list has to loop.
+        let mut at = item;
+        for _ in 0..=items.len() {
+            at = match at.parent {
+                None => break,
+                Some(p) if p == item.index => return Err(bad(item, "is its own ancestor")),
+                Some(p) => find(p).ok_or_else(|| bad(at, &format!("missing parent {}", p)))?,
+            };
+        }
+        if at.parent.is_some() {
+            return Err(bad(item, "has a parent cycle above it"));
+        }
+    }
+    Ok(())
+}
+
+/// Replaces `path` with `contents` so that readers see either the old or
+/// the new file, never a mix, even across a crash.
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp = PathBuf::from(tmp_name);
+
+    let mut file = File::create(&tmp)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+
+    // Make the rename itself durable.
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "todo_swamp-{}-{}.journal",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_journal_roundtrip() {
+        let path = temp_path("roundtrip");
+        let mut ws = Workspace::new();
+        let mut journal = Journal::open(&path, &mut ws).unwrap();
+        let mut at = Timestamp::from(Date::new(2026, 10, 15).unwrap());
+
+        for q in &[
+            Query::Add(
+                Description::new("buy bread"),
+                Tag::from_strings(vec!["groceries"]),
+                Attributes {
+                    priority: Some(Priority::High),
+                    due: Date::new(2026, 11, 1).map(DateExpr::On),
+                    recurrence: Some(Recurrence::MonthlyOn(1)),
+                    parent: None,
+                    after: vec![],
+                    cleared: vec![],
+                },
+            ),
+            Query::Add(
+                Description::new("call \"parents\", re: C:\\trip"),
+                vec![],
+                Attributes::default(),
+            ),
+            Query::Done(Index::new(0)),
+            Query::Use(String::from("work")),
+            Query::Add(Description::new("fix build"), vec![], Attributes::default()),
+            Query::Use(String::from(DEFAULT_LIST)),
+            Query::Move(Index::new(1), String::from("work")),
+        ] {
+            at = Timestamp::new(at.seconds() + 3600);
+            ws.set_clock(Clock::Fixed(at));
+            runner::run_query(q.clone(), &mut ws).unwrap();
+            journal.append(q, at).unwrap();
+        }
+        drop(journal);
+
+        let mut replayed = Workspace::new();
+        Journal::open(&path, &mut replayed).unwrap();
+        assert_eq!(replayed, ws);
+        assert_eq!(replayed.clock(), Clock::System);
+        let tl = replayed.current();
+        assert_eq!(
+            tl.get(Index::new(0)).unwrap().completed_at,
+            "2026-10-15T03:00:00Z".parse().ok()
+        );
+        // Doing the recurring item added its next occurrence.
+        assert_eq!(tl.get(Index::new(0)).unwrap().next, Some(Index::new(2)));
+        assert_eq!(
+            replayed
+                .get("work")
+                .unwrap()
+                .get(Index::new(1))
+                .unwrap()
+                .description(),
+            Description::new("call \"parents\", re: C:\\trip")
+        );
+        assert_eq!(
+            replayed
+                .current_mut()
+                .push(Description::new("next"), vec![]),
+            Index::new(3)
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_journal_drops_truncated_tail() {
+        let path = temp_path("truncated");
+        fs::write(&path, "add \"buy bread\" #groceries\nadd \"buy mi").unwrap();
+
+        let mut ws = Workspace::new();
+        let mut journal = Journal::open(&path, &mut ws).unwrap();
+        assert_eq!(journal.dropped_entry(), Some("add \"buy mi"));
+        let at = "2026-10-15T09:30:00Z".parse().unwrap();
+        journal.append(&Query::Done(Index::new(0)), at).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "add \"buy bread\" #groceries\n@2026-10-15T09:30:00Z done 0\n"
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_journal_rejects_corrupt_entry() {
+        let path = temp_path("corrupt");
+        fs::write(&path, "add \"buy bread\"\ndone 7\n").unwrap();
+
+        let mut ws = Workspace::new();
+        let err = Journal::open(&path, &mut ws).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        fs::write(&path, "@2026-10-15 add \"buy bread\"\n").unwrap();
+        let err = Journal::open(&path, &mut ws).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_journal_only_holds_writable_dates() {
+        let path = temp_path("date-range");
+        let mut ws = Workspace::new();
+        ws.set_clock(Clock::Fixed("9999-12-30T12:00:00Z".parse().unwrap()));
+        let mut journal = Journal::open(&path, &mut ws).unwrap();
+        let opts = runner::Options::default();
+
+        for (line, result) in &[
+            ("add \"a\" due:+1d", Ok(())),
+            (
+                "add \"b\" due:+2d",
+                Err(QueryError::DateOutOfRange(Date::MAX.add_days(1))),
+            ),
+            (
+                "add \"c\" due:+4000000d",
+                Err(QueryError::DateOutOfRange(Date::new(20951, 8, 16).unwrap())),
+            ),
+            (
+                "edit 0 \"a\" due:+1w",
+                Err(QueryError::DateOutOfRange(Date::new(10000, 1, 6).unwrap())),
+            ),
+        ] {
+            assert_eq!(
+                &runner::run_line(1, line, &mut ws, Some(&mut journal), &opts),
+                result,
+                "{}",
+                line
+            );
+        }
+        drop(journal);
+
+        let mut reloaded = Workspace::new();
+        Journal::open(&path, &mut reloaded).unwrap();
+        let item = reloaded.current().get(Index::new(0)).unwrap();
+        assert_eq!(item.due, Some(Date::MAX));
+        assert!(reloaded.current().get(Index::new(1)).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_journal_keeps_the_time_queries_ran_at() {
+        let path = temp_path("clock");
+        let mut ws = Workspace::new();
+        let mut journal = Journal::open(&path, &mut ws).unwrap();
+        let opts = runner::Options::default();
+        for line in &["add \"water plants\" every:day", "done 0"] {
+            runner::run_line(1, line, &mut ws, Some(&mut journal), &opts).unwrap();
+        }
+        assert_eq!(ws.clock(), Clock::System);
+        drop(journal);
+
+        let mut reloaded = Workspace::new();
+        Journal::open(&path, &mut reloaded).unwrap();
+        assert_eq!(reloaded, ws);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_compact_then_reload() {
+        let path = temp_path("compact");
+        let mut ws = Workspace::new();
+        let mut journal = Journal::open(&path, &mut ws).unwrap();
+
+        for line in &[
+            "add \"buy bread\" #groceries",
+            "add \"buy milk\"",
+            "done 0",
+            "use work",
+            "add \"fix build\"",
+        ] {
+            let (_, q) = parser::query(line).unwrap();
+            runner::run_query(q.clone(), &mut ws).unwrap();
+            journal.append(&q, ws.now()).unwrap();
+        }
+        journal.compact(&ws).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# generation 1\n");
+
+        let q = Query::Add(
+            Description::new("call parents"),
+            vec![],
+            Attributes::default(),
+        );
+        runner::run_query(q.clone(), &mut ws).unwrap();
+        journal.append(&q, ws.now()).unwrap();
+        drop(journal);
+
+        let mut reloaded = Workspace::new();
+        Journal::open(&path, &mut reloaded).unwrap();
+        assert_eq!(reloaded, ws);
+        assert_eq!(reloaded.current_name(), "work");
+
+        fs::remove_file(snapshot_path(&path)).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stale_journal_is_not_replayed_twice() {
+        let path = temp_path("stale");
+        let mut ws = Workspace::new();
+        ws.current_mut().push(Description::new("buy bread"), vec![]);
+        // A compaction that crashed after installing the snapshot but before
+        // resetting the journal.
+        fs::write(snapshot_path(&path), render_snapshot(1, &ws)).unwrap();
+        fs::write(&path, "add \"buy bread\"\n").unwrap();
+
+        let mut reloaded = Workspace::new();
+        Journal::open(&path, &mut reloaded).unwrap();
+        assert_eq!(reloaded, ws);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# generation 1\n");
+
+        fs::remove_file(snapshot_path(&path)).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_keeps_deleted_indices() {
+        let mut ws = Workspace::new();
+        ws.use_list("empty");
+        ws.use_list("work");
+        let tl = ws.current_mut();
+        tl.push(Description::new("buy bread"), vec![]);
+        tl.push(Description::new("buy milk"), vec![]);
+        tl.push(Description::new("call parents"), vec![]);
+        tl.delete(Index::new(0)).unwrap();
+        tl.delete(Index::new(2)).unwrap();
+        tl.done_with_index(Index::new(1)).unwrap();
+
+        let path = temp_path("deleted");
+        fs::write(snapshot_path(&path), render_snapshot(0, &ws)).unwrap();
+        let (_, restored) = read_snapshot(&snapshot_path(&path)).unwrap().unwrap();
+        assert_eq!(restored, ws);
+
+        fs::remove_file(snapshot_path(&path)).unwrap();
+    }
+
+    #[test]
+    fn test_compact_refuses_unreadable_snapshot() {
+        let path = temp_path("unreadable");
+        let mut ws = Workspace::new();
+        let mut journal = Journal::open(&path, &mut ws).unwrap();
+        let q = Query::Add(Description::new("buy bread"), vec![], Attributes::default());
+        runner::run_query(q.clone(), &mut ws).unwrap();
+        journal.append(&q, ws.now()).unwrap();
+        let journaled = fs::read_to_string(&path).unwrap();
+
+        // Only a bug could get such a date into the list.
+        let mut item = TodoItem::create(Index::new(0), &Description::new("x"), &[], false);
+        item.due = Date::new(10000, 1, 15);
+        let unwritable = Workspace::restore(
+            DEFAULT_LIST,
+            vec![(
+                String::from(DEFAULT_LIST),
+                TodoList::restore(Index::new(1), vec![item]),
+            )],
+        );
+        let err = journal.compact(&unwritable).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(!snapshot_path(&path).exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), journaled);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_rejects_bad_indices() {
+        let header = "todo-snapshot 2\ngeneration 1\ncurrent default\n";
+        for body in &[
+            "list default 99999999999999999\n",
+            "list default 1\n0 open \"a\"\n1 open \"b\"\n",
+            "list default 2\n0 open \"a\" parent:5\n",
+            "list default 2\n0 open \"a\" after:1\n",
+            "list default 2\n0 open \"a\" parent:0\n",
+            "list default 2\n0 open \"a\" parent:1\n1 open \"b\" parent:0\n",
+            "list default 3\n0 open \"a\" parent:1\n1 open \"b\" parent:2\n\
+             2 open \"c\" parent:1\n",
+        ] {
+            let err = parse_snapshot(&format!("{}{}", header, body)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", body);
+        }
+        let err = parse_snapshot("todo-snapshot 1\ngeneration 1\ntop-index 99999999999\n");
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // A parent may come after its subtasks.
+        let body = "list default 2\n0 open \"a\" parent:1\n1 open \"b\"\n";
+        let (_, ws) = parse_snapshot(&format!("{}{}", header, body)).unwrap();
+        assert_eq!(ws.current().tree(StatusFilter::Open).len(), 2);
+    }
+
+    #[test]
+    fn test_single_list_snapshot() {
+        let path = temp_path("single");
+        fs::write(
+            snapshot_path(&path),
+            "todo-snapshot 1\ngeneration 3\ntop-index 2\n1 open \"buy milk\" #groceries\n",
+        )
+        .unwrap();
+        let (generation, ws) = read_snapshot(&snapshot_path(&path)).unwrap().unwrap();
+        assert_eq!(generation, 3);
+        assert_eq!(ws.current_name(), DEFAULT_LIST);
+        assert_eq!(ws.current().top_index(), Index::new(2));
+        assert_eq!(
+            ws.current().get(Index::new(1)).unwrap().tags(),
+            Tag::from_strings(vec!["groceries"])
+        );
+
+        fs::remove_file(snapshot_path(&path)).unwrap();
+    }
+}
@@ -1,4 +1,7 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
 use std::fmt;
+use std::slice;
 
 use rayon::prelude::*;
 
@@ -38,6 +41,47 @@ impl Description {
     pub fn value(&self) -> &str {
         &self.0
     }
+
+    /// The description as written in a query: in double quotes, with `"`
+    /// and `\` escaped.
+    pub fn quoted(&self) -> String {
+        let mut buff = String::from("\"");
+        for c in self.0.chars() {
+            push_escaped(&mut buff, c);
+        }
+        buff.push('"');
+        buff
+    }
+}
+
+/// Pushes `c` as it is written inside a quoted description.
+pub(crate) fn push_escaped(buff: &mut String, c: char) {
+    if c == '"' || c == '\\' {
+        buff.push('\\');
+    }
+    buff.push(c);
+}
+
+/// The searchable words of a description, i.e. the runs of letters, digits
+/// and `-`, each with the char offset it starts at.
+fn tokenize(text: &str) -> Vec<(usize, &str)> {
+    let mut words = vec![];
+    let mut start: Option<(usize, usize)> = None;
+    for (n, (i, c)) in text.char_indices().enumerate() {
+        let in_word = c.is_alphanumeric() || c == '-';
+        match start {
+            None if in_word => start = Some((n, i)),
+            Some((n0, i0)) if !in_word => {
+                words.push((n0, &text[i0..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some((n0, i0)) = start {
+        words.push((n0, &text[i0..]));
+    }
+    words
 }
 
 impl fmt::Display for Description {
@@ -46,6 +90,7 @@ impl fmt::Display for Description {
     }
 }
 
+/// A tag, which may be a path of levels like `work/backend/auth`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Tag(String);
 
@@ -58,8 +103,28 @@ impl Tag {
         &self.0
     }
 
+    /// The levels of the path, outermost first.
+    pub fn levels(&self) -> impl Iterator<Item = &str> {
+        self.0.split('/')
+    }
+
+    /// The last level, e.g. `auth` for `work/backend/auth`.
+    pub fn name(&self) -> &str {
+        self.levels().last().unwrap_or("")
+    }
+
+    /// The tag itself and the tags above it, outermost first.
+    pub fn ancestors_and_self(&self) -> Vec<Tag> {
+        self.0
+            .char_indices()
+            .filter(|&(_, c)| c == '/')
+            .map(|(i, _)| Tag::new(&self.0[..i]))
+            .chain(Some(self.clone()))
+            .collect()
+    }
+
     pub fn from_strings(ss: Vec<&str>) -> Vec<Tag> {
-        ss.clone().into_iter().map(|s| Tag::new(s)).collect()
+        ss.into_iter().map(Tag::new).collect()
     }
 }
 
@@ -69,12 +134,186 @@ impl fmt::Display for Tag {
     }
 }
 
+/// A tag and the tags below it, with the number of items tagged with any
+/// of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagNode {
+    pub tag: Tag,
+    pub items: usize,
+    pub children: Vec<TagNode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Priority::Low => write!(f, "low"),
+            Priority::Medium => write!(f, "medium"),
+            Priority::High => write!(f, "high"),
+        }
+    }
+}
+
+/// How often a recurring item comes back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recurrence {
+    Days(u32),
+    Weeks(u32),
+    /// On the same day of the month as the due date, or the last day of
+    /// shorter months. Once an item has a due date this is kept as
+    /// `MonthlyOn` that day, see `anchored`, so that a short month does not
+    /// pull the rest of the series earlier.
+    Monthly,
+    /// On this day of the month, or the last day of shorter months.
+    MonthlyOn(u32),
+}
+
+impl Recurrence {
+    /// The rule for an item due on `due`: `Monthly` becomes monthly on the
+    /// day of `due`, the others stay as they are.
+    pub fn anchored(self, due: Option<Date>) -> Recurrence {
+        match (self, due) {
+            (Recurrence::Monthly, Some(d)) => Recurrence::MonthlyOn(d.day()),
+            (rule, _) => rule,
+        }
+    }
+
+    /// The first day of the series after `date`.
+    pub fn after(self, date: Date) -> Date {
+        match self {
+            Recurrence::Days(n) => date.add_days(i64::from(n)),
+            Recurrence::Weeks(n) => date.add_days(7 * i64::from(n)),
+            Recurrence::Monthly => date.add_months(1),
+            Recurrence::MonthlyOn(day) => {
+                let on = |d: Date| Date::new(d.year(), d.month(), day.min(d.end_of_month().day()));
+                match on(date) {
+                    Some(d) if d > date => d,
+                    _ => on(date.end_of_month().add_days(1)).unwrap_or(date),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Recurrence::Days(1) => write!(f, "day"),
+            Recurrence::Days(n) => write!(f, "{}d", n),
+            Recurrence::Weeks(1) => write!(f, "week"),
+            Recurrence::Weeks(n) => write!(f, "{}w", n),
+            Recurrence::Monthly => write!(f, "month"),
+            Recurrence::MonthlyOn(day) => write!(f, "month-on-{}", day),
+        }
+    }
+}
+
+/// Everything about an item that is given after its tags, e.g. `!high`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Attributes {
+    pub priority: Option<Priority>,
+    pub due: Option<DateExpr>,
+    pub recurrence: Option<Recurrence>,
+    /// The item this one is a subtask of.
+    pub parent: Option<Index>,
+    /// The items that have to be done before this one.
+    pub after: Vec<Index>,
+    /// The attributes given as `none`, e.g. `due:none`, which an edit
+    /// removes instead of keeping.
+    pub cleared: Vec<AttributeKind>,
+}
+
+/// Which one of the `Attributes` a word is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeKind {
+    Priority,
+    Due,
+    Recurrence,
+    Parent,
+    After,
+}
+
+impl Attributes {
+    pub fn resolve(self, today: Date) -> Result<Attributes, QueryError> {
+        let due = match self.due.map(|d| d.resolve(today)) {
+            Some(d) if !d.is_writable() => return Err(QueryError::DateOutOfRange(d)),
+            due => due,
+        };
+        Ok(Attributes {
+            due: due.map(DateExpr::On),
+            recurrence: self.recurrence.map(|r| r.anchored(due)),
+            ..self
+        })
+    }
+
+    /// Whether `kind` was given as `none`.
+    pub fn clears(&self, kind: AttributeKind) -> bool {
+        self.cleared.contains(&kind)
+    }
+
+    /// The attributes as written in a query, one word each.
+    pub fn to_words(&self) -> Vec<String> {
+        let mut words = vec![];
+        match self.priority {
+            Some(p) => words.push(format!("!{}", p)),
+            None if self.clears(AttributeKind::Priority) => words.push(String::from("!none")),
+            None => {}
+        }
+        match self.due {
+            Some(d) => words.push(format!("due:{}", d)),
+            None if self.clears(AttributeKind::Due) => words.push(String::from("due:none")),
+            None => {}
+        }
+        match self.recurrence {
+            Some(r) => words.push(format!("every:{}", r)),
+            None if self.clears(AttributeKind::Recurrence) => {
+                words.push(String::from("every:none"))
+            }
+            None => {}
+        }
+        match self.parent {
+            Some(i) => words.push(format!("parent:{}", i)),
+            None if self.clears(AttributeKind::Parent) => words.push(String::from("parent:none")),
+            None => {}
+        }
+        if self.clears(AttributeKind::After) {
+            words.push(String::from("after:none"));
+        }
+        for i in &self.after {
+            words.push(format!("after:{}", i));
+        }
+        words
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TodoItem {
     pub index: Index,
+    /// The words of the description, as searched.
     pub description: Vec<String>,
     pub tags: Vec<String>,
     pub done: bool,
+    pub priority: Option<Priority>,
+    pub due: Option<Date>,
+    /// `None` for items from before creation times were recorded.
+    pub created_at: Option<Timestamp>,
+    /// When the item was last marked done, while it is done.
+    pub completed_at: Option<Timestamp>,
+    pub recurrence: Option<Recurrence>,
+    /// The first item of the series this one recurs from.
+    pub series: Option<Index>,
+    /// The occurrence that was added when this one was done.
+    pub next: Option<Index>,
+    pub parent: Option<Index>,
+    /// The items this one waits for, see `TodoList::block`.
+    pub after: Vec<Index>,
+    text: String,
     words_hash: Vec<u32>,
     tags_hash: Vec<u32>,
 }
@@ -90,56 +329,161 @@ impl TodoItem {
     ) -> TodoItem {
         TodoItem {
             index,
+            text: description.join(" "),
             description,
             tags,
             done,
+            priority: None,
+            due: None,
+            created_at: None,
+            completed_at: None,
+            recurrence: None,
+            series: None,
+            next: None,
+            parent: None,
+            after: vec![],
             words_hash,
             tags_hash,
         }
     }
+
+    /// Builds an item from query-level values, computing the search hashes.
+    pub fn create(index: Index, description: &Description, tags: &[Tag], done: bool) -> TodoItem {
+        let mut item = TodoItem::new(index, vec![], vec![], done, vec![], vec![]);
+        item.set_description(description);
+        item.set_tags(tags);
+        item
+    }
+
+    pub fn set_description(&mut self, description: &Description) {
+        self.text = description.value().to_owned();
+        self.description = tokenize(&self.text)
+            .into_iter()
+            .map(|(_, w)| w.to_owned())
+            .collect();
+        self.words_hash = hash_words(&self.description);
+    }
+
+    pub fn set_tags(&mut self, tags: &[Tag]) {
+        self.tags = tags.iter().map(|x| x.value().to_owned()).collect();
+        self.tags_hash = hash_words(&self.tags);
+    }
+
+    /// Relative dates in `attributes` are taken relative to `today`.
+    pub fn set_attributes(&mut self, attributes: &Attributes, today: Date) {
+        self.priority = attributes.priority;
+        self.due = attributes.due.map(|d| d.resolve(today));
+        self.recurrence = attributes.recurrence.map(|r| r.anchored(self.due));
+        self.parent = attributes.parent;
+        self.after = attributes.after.clone();
+    }
+
+    pub fn attributes(&self) -> Attributes {
+        Attributes {
+            priority: self.priority,
+            due: self.due.map(DateExpr::On),
+            recurrence: self.recurrence,
+            parent: self.parent,
+            after: self.after.clone(),
+            cleared: vec![],
+        }
+    }
+
+    /// The date of `field`, if the item has one.
+    pub fn date(&self, field: DateField) -> Option<Date> {
+        match field {
+            DateField::Due => self.due,
+            DateField::Created => self.created_at.map(Timestamp::date),
+            DateField::Completed => self.completed_at.map(Timestamp::date),
+        }
+    }
+
+    pub fn description(&self) -> Description {
+        Description::new(&self.text)
+    }
+
+    pub fn tags(&self) -> Vec<Tag> {
+        self.tags.iter().map(|t| Tag::new(t)).collect()
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Items are stored at the position of their index. Deleting an item leaves
+/// a `None` tombstone behind, so indices are never reused.
+#[derive(Debug, Clone)]
 pub struct TodoList {
     top_index: Index,
-    items: Vec<TodoItem>,
+    items: Vec<Option<TodoItem>>,
+    clock: Clock,
+    /// Whether `done` refuses items that have open subtasks.
+    subtasks_block_done: bool,
 }
 
+/// Lists are equal when they hold the same items, whatever their clocks
+/// and rules.
+impl PartialEq for TodoList {
+    fn eq(&self, other: &TodoList) -> bool {
+        self.top_index == other.top_index && self.items == other.items
+    }
+}
+
+impl Eq for TodoList {}
+
+/// Simple, one-to-one case folding, so that a folded word keeps the char
+/// positions of the original.
 #[inline]
-fn get_alphabet_position(c: u8) -> u8 {
-    if c >= 'a' as u8 && c <= 'z' as u8 {
-        c - 'a' as u8
-    } else if c >= 'A' as u8 && c <= 'Z' as u8 {
-        c - 'A' as u8
-    } else if c == '-' as u8 {
-        28
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[inline]
+fn fold_str(s: &str) -> String {
+    s.chars().map(fold).collect()
+}
+
+/// An attribute after an edit: the one `attrs` gives, or none if it clears
+/// it, or else the item's `current` one.
+fn edited<T>(
+    attrs: &Attributes,
+    kind: AttributeKind,
+    given: Option<T>,
+    current: Option<T>,
+) -> Option<T> {
+    if attrs.clears(kind) {
+        None
     } else {
-        127 // undefined behaviour
+        given.or(current)
     }
 }
 
+/// Whether two tags are the same but for case.
+fn same_tag(a: &Tag, b: &Tag) -> bool {
+    fold_str(a.value()) == fold_str(b.value())
+}
+
+/// The bit of a position hash standing for `c`, after folding. Each of a-z
+/// has its own bit; digits, `-` and all other chars share the remaining six.
+/// Sharing a bit only lets more items through the prefilter, so the hash
+/// never rules out an item that matches.
 #[inline]
-fn get_bit_position(c: u8) -> u32 {
-    if c >= 'a' as u8 && c <= 'z' as u8 {
-        1 << c - 'a' as u8
-    } else if c >= 'A' as u8 && c <= 'Z' as u8 {
-        1 << c - 'A' as u8
-    } else if c == '-' as u8 {
-        1 << 28
-    } else {
-        0
+fn get_bit_position(c: char) -> u32 {
+    let c = fold(c);
+    match c {
+        'a'..='z' => 1 << (c as u32 - 'a' as u32),
+        '0'..='9' => 1 << 26,
+        '-' => 1 << 27,
+        _ => 1 << (28 + c as u32 % 4),
     }
 }
 
 #[inline]
-fn hash_words(words: &Vec<String>) -> Vec<u32> {
+fn hash_words(words: &[String]) -> Vec<u32> {
     let mut words_hash: Vec<u32> = Vec::new();
     for word in words {
         for (i, c) in word.chars().enumerate() {
             if i >= words_hash.len() {
-                words_hash.push(get_bit_position(c as u8));
+                words_hash.push(get_bit_position(c));
             } else {
-                words_hash[i] |= get_bit_position(c as u8);
+                words_hash[i] |= get_bit_position(c);
             }
         }
     }
@@ -147,12 +491,12 @@ fn hash_words(words: &Vec<String>) -> Vec<u32> {
 }
 
 #[inline]
-fn match_with_hash(word: &String, words_hash: &Vec<u32>) -> bool {
+fn match_with_hash(word: &str, words_hash: &[u32]) -> bool {
     let mut i: usize = 0;
     let m = words_hash.len();
 
     for c in word.chars() {
-        let pos = get_bit_position(c as u8);
+        let pos = get_bit_position(c);
         while i < m && (words_hash[i] & pos == 0) {
             i += 1;
         }
@@ -165,35 +509,291 @@ fn match_with_hash(word: &String, words_hash: &Vec<u32>) -> bool {
     true
 }
 
+/// Prefilter for `Prefix` and `Exact`: the pattern has to line up with the
+/// start of some word, so its i-th char must appear at position i.
 #[inline]
-fn is_subsequence(pattern: &String, sequence: &String) -> bool {
-    let mut i: usize = 0;
-    let m = sequence.len();
-    let bytes = sequence.as_bytes();
+fn match_prefix_with_hash(word: &str, words_hash: &[u32]) -> bool {
+    word.chars().enumerate().all(|(i, c)| {
+        i < words_hash.len() && words_hash[i] & get_bit_position(c) != 0
+    })
+}
 
-    for c in pattern.chars() {
-        let pos = get_alphabet_position(c as u8);
-        while i < m && (get_alphabet_position(bytes[i]) != pos) {
-            i += 1;
-        }
-        if i >= m {
-            return false;
-        }
-        i += 1;
+/// Prefilter for `Substring`: the pattern lines up with some offset.
+#[inline]
+fn match_substring_with_hash(word: &str, words_hash: &[u32]) -> bool {
+    let n = word.chars().count();
+    n == 0
+        || (0..words_hash.len().saturating_sub(n - 1)).any(|offset| {
+            word.chars()
+                .enumerate()
+                .all(|(i, c)| words_hash[offset + i] & get_bit_position(c) != 0)
+        })
+}
+
+#[inline]
+fn match_with_hash_in_mode(mode: MatchMode, word: &str, words_hash: &[u32]) -> bool {
+    match mode {
+        MatchMode::Subsequence => match_with_hash(word, words_hash),
+        MatchMode::Prefix | MatchMode::Exact => match_prefix_with_hash(word, words_hash),
+        MatchMode::Substring => match_substring_with_hash(word, words_hash),
     }
-    true
 }
 
 #[inline]
-fn match_word_deterministic(pattern: &String, words: &Vec<String>) -> bool {
+fn is_subsequence(pattern: &str, sequence: &str) -> bool {
+    let mut sequence = sequence.chars().map(fold);
+    pattern.chars().map(fold).all(|c| sequence.any(|x| x == c))
+}
+
+#[inline]
+fn match_word_deterministic(pattern: &str, words: &[String]) -> bool {
     words.iter().any(|x| is_subsequence(pattern, x))
 }
 
 #[inline]
-fn match_words(patterns: &Vec<String>, words_hash: &Vec<u32>, words: &Vec<String>) -> bool {
-    patterns
+fn match_word_in_mode(mode: MatchMode, pattern: &str, words: &[String]) -> bool {
+    let pattern = fold_str(pattern);
+    match mode {
+        MatchMode::Subsequence => match_word_deterministic(&pattern, words),
+        MatchMode::Prefix => words.iter().any(|x| fold_str(x).starts_with(&pattern)),
+        MatchMode::Substring => words.iter().any(|x| fold_str(x).contains(&pattern)),
+        MatchMode::Exact => words.iter().any(|x| fold_str(x) == pattern),
+    }
+}
+
+#[inline]
+fn match_words(
+    mode: MatchMode,
+    patterns: &[String],
+    words_hash: &[u32],
+    words: &[String],
+) -> bool {
+    patterns.iter().all(|word| {
+        match_with_hash_in_mode(mode, word, words_hash) && match_word_in_mode(mode, word, words)
+    })
+}
+
+/// Char positions in `tag` that `pattern` matches in `mode`, or `None` if it
+/// does not match. Each level of the pattern has to match the level of the
+/// tag at the same depth, so a pattern matches its tag and all tags below.
+fn tag_positions(mode: MatchMode, pattern: &str, tag: &str) -> Option<Vec<usize>> {
+    let mut levels = tag.split('/');
+    let mut positions = vec![];
+    let mut offset = 0;
+    for (depth, p) in pattern.split('/').enumerate() {
+        let level = levels.next()?;
+        if depth > 0 {
+            // The slash before this level.
+            positions.push(offset - 1);
+        }
+        positions.extend(match_positions(mode, p, level)?.into_iter().map(|i| offset + i));
+        offset += level.chars().count() + 1;
+    }
+    Some(positions)
+}
+
+/// Whether `pattern` matches one of the tags, see `tag_positions`. A match
+/// level by level is also a subsequence of the whole tag, so the
+/// subsequence prefilter holds in every mode.
+#[inline]
+fn match_tag(mode: MatchMode, pattern: &str, tags_hash: &[u32], tags: &[String]) -> bool {
+    match_with_hash(pattern, tags_hash)
+        && tags.iter().any(|t| tag_positions(mode, pattern, t).is_some())
+}
+
+/// Char positions in `word` that `pattern` matches in `mode`, or `None` if
+/// it does not match. For subsequences the tightest alignment is picked:
+/// the one with the most adjacent matches, preferring earlier starts.
+fn match_positions(mode: MatchMode, pattern: &str, word: &str) -> Option<Vec<usize>> {
+    let pattern: Vec<char> = pattern.chars().map(fold).collect();
+    let word: Vec<char> = word.chars().map(fold).collect();
+    let n = pattern.len();
+
+    match mode {
+        MatchMode::Exact if word == pattern => Some((0..n).collect()),
+        MatchMode::Prefix if word.starts_with(&pattern) => Some((0..n).collect()),
+        MatchMode::Substring => (0..(word.len() + 1).saturating_sub(n))
+            .find(|&start| word[start..start + n] == pattern[..])
+            .map(|start| (start..start + n).collect()),
+        MatchMode::Subsequence => {
+            if n == 0 {
+                return Some(vec![]);
+            }
+            let mut best: Option<Vec<usize>> = None;
+            for start in (0..word.len()).filter(|&i| word[i] == pattern[0]) {
+                let mut positions = vec![start];
+                for c in &pattern[1..] {
+                    let from = positions[positions.len() - 1] + 1;
+                    match word[from..].iter().position(|x| x == c) {
+                        Some(i) => positions.push(from + i),
+                        None => break,
+                    }
+                }
+                let better = match &best {
+                    Some(b) => adjacent_pairs(&positions) > adjacent_pairs(b),
+                    None => true,
+                };
+                if positions.len() == n && better {
+                    best = Some(positions);
+                }
+            }
+            best
+        }
+        _ => None,
+    }
+}
+
+fn adjacent_pairs(positions: &[usize]) -> usize {
+    positions.windows(2).filter(|w| w[1] == w[0] + 1).count()
+}
+
+/// How well `pattern` matches its best word: hits at the start of a word
+/// and runs of adjacent letters score higher, an exact word the highest.
+fn word_score(
+    pattern: &str,
+    words: &[String],
+    positions: impl Fn(&str, &str) -> Option<Vec<usize>>,
+) -> u32 {
+    words
         .iter()
-        .all(|word| match_with_hash(word, words_hash) && match_word_deterministic(word, words))
+        .filter_map(|w| {
+            positions(pattern, w).map(|ps| {
+                let mut score = 10 + 10 * adjacent_pairs(&ps) as u32;
+                if ps.first() == Some(&0) {
+                    score += 20;
+                }
+                if ps.len() == w.chars().count() {
+                    score += 50;
+                }
+                score
+            })
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Relevance of `item` for `expr`; only terms that are not negated count.
+fn score_expr(mode: MatchMode, expr: &SearchExpr, item: &TodoItem) -> u32 {
+    match expr {
+        SearchExpr::Word(w) => word_score(w.value(), &item.description, |p, w| {
+            match_positions(mode, p, w)
+        }),
+        SearchExpr::Tag(t) => {
+            let s = word_score(t.value(), &item.tags, |p, t| tag_positions(mode, p, t));
+            if s > 0 {
+                s + 25
+            } else {
+                0
+            }
+        }
+        SearchExpr::Priority(_, _)
+        | SearchExpr::Date(_, _, _)
+        | SearchExpr::DateWithin(_, _)
+        | SearchExpr::Overdue
+        | SearchExpr::Ready
+        | SearchExpr::Blocked
+        | SearchExpr::Not(_) => 0,
+        SearchExpr::And(es) | SearchExpr::Or(es) => {
+            es.iter().map(|e| score_expr(mode, e, item)).sum()
+        }
+    }
+}
+
+/// Adds the positions matched by the terms of `expr` that are not negated
+/// to `words` and `tags`, which hold one list per description word and tag.
+fn collect_matches(
+    mode: MatchMode,
+    expr: &SearchExpr,
+    item: &TodoItem,
+    words: &mut Vec<Vec<usize>>,
+    tags: &mut Vec<Vec<usize>>,
+) {
+    let add = |positions: &dyn Fn(&str) -> Option<Vec<usize>>,
+               targets: &[String],
+               found: &mut Vec<Vec<usize>>| {
+        for (i, target) in targets.iter().enumerate() {
+            if let Some(ps) = positions(target) {
+                found[i].extend(ps);
+                found[i].sort_unstable();
+                found[i].dedup();
+            }
+        }
+    };
+    match expr {
+        SearchExpr::Word(w) => add(
+            &|target| match_positions(mode, w.value(), target),
+            &item.description,
+            words,
+        ),
+        SearchExpr::Tag(t) => add(
+            &|target| tag_positions(mode, t.value(), target),
+            &item.tags,
+            tags,
+        ),
+        SearchExpr::Priority(_, _)
+        | SearchExpr::Date(_, _, _)
+        | SearchExpr::DateWithin(_, _)
+        | SearchExpr::Overdue
+        | SearchExpr::Ready
+        | SearchExpr::Blocked
+        | SearchExpr::Not(_) => {}
+        SearchExpr::And(es) | SearchExpr::Or(es) => {
+            for e in es {
+                collect_matches(mode, e, item, words, tags);
+            }
+        }
+    }
+}
+
+/// Evaluates `expr` against one item. Every word and tag goes through the
+/// hash prefilter first; that also holds under `Not`, since a prefilter miss
+/// is a definite miss.
+fn match_expr(
+    mode: MatchMode,
+    expr: &SearchExpr,
+    item: &TodoItem,
+    tl: &TodoList,
+    today: Date,
+) -> bool {
+    match expr {
+        SearchExpr::Word(w) => match_words(
+            mode,
+            slice::from_ref(&w.0),
+            &item.words_hash,
+            &item.description,
+        ),
+        SearchExpr::Tag(t) => match_tag(mode, t.value(), &item.tags_hash, &item.tags),
+        SearchExpr::Priority(cmp, p) => match item.priority {
+            Some(ip) => cmp.holds(ip, *p),
+            None => false,
+        },
+        SearchExpr::Date(field, cmp, d) => match item.date(*field) {
+            Some(date) => cmp.holds(date, d.resolve(today)),
+            None => false,
+        },
+        SearchExpr::DateWithin(field, p) => {
+            let (first, last) = p.range(today);
+            match item.date(*field) {
+                Some(date) => first <= date && date <= last,
+                None => false,
+            }
+        }
+        SearchExpr::Overdue => match item.due {
+            Some(due) => !item.done && due < today,
+            None => false,
+        },
+        SearchExpr::Ready => !tl.is_blocked(item),
+        SearchExpr::Blocked => tl.is_blocked(item),
+        SearchExpr::Not(e) => !match_expr(mode, e, item, tl, today),
+        SearchExpr::And(es) => es.iter().all(|e| match_expr(mode, e, item, tl, today)),
+        SearchExpr::Or(es) => es.iter().any(|e| match_expr(mode, e, item, tl, today)),
+    }
+}
+
+impl Default for TodoList {
+    fn default() -> TodoList {
+        TodoList::new()
+    }
 }
 
 impl TodoList {
@@ -201,73 +801,589 @@ impl TodoList {
         TodoList {
             top_index: Index::new(0),
             items: vec![],
+            clock: Clock::System,
+            subtasks_block_done: true,
+        }
+    }
+
+    /// Rebuilds a list from its parts, e.g. when loading a snapshot. Every
+    /// index below `top_index` without an item is taken to be deleted.
+    ///
+    /// The items have to be below `top_index`, with parents and dependencies
+    /// among them; the snapshot reader checks this before calling it.
+    pub fn restore(top_index: Index, items: Vec<TodoItem>) -> TodoList {
+        let mut slots: Vec<Option<TodoItem>> = vec![None; top_index.value() as usize];
+        for item in items {
+            let i = item.index.value() as usize;
+            slots[i] = Some(item);
         }
+        TodoList {
+            top_index,
+            items: slots,
+            clock: Clock::System,
+            subtasks_block_done: true,
+        }
+    }
+
+    pub fn clock(&self) -> Clock {
+        self.clock
+    }
+
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    pub fn now(&self) -> Timestamp {
+        self.clock.now()
+    }
+
+    pub fn today(&self) -> Date {
+        self.clock.today()
+    }
+
+    pub fn subtasks_block_done(&self) -> bool {
+        self.subtasks_block_done
+    }
+
+    /// Whether an item can only be marked done once all of its subtasks
+    /// are; on by default.
+    pub fn set_subtasks_block_done(&mut self, block: bool) {
+        self.subtasks_block_done = block;
+    }
+
+    pub fn top_index(&self) -> Index {
+        self.top_index
+    }
+
+    /// The items that have not been deleted, by index.
+    pub fn items(&self) -> impl Iterator<Item = &TodoItem> {
+        self.items.iter().flatten()
+    }
+
+    pub fn get(&self, idx: Index) -> Result<&TodoItem, QueryError> {
+        match self.items.get(idx.value() as usize) {
+            None => Err(QueryError::InvalidIndex(idx)),
+            Some(None) => Err(QueryError::Deleted(idx)),
+            Some(Some(item)) => Ok(item),
+        }
+    }
+
+    /// The direct subtasks of the item, by index.
+    pub fn children(&self, idx: Index) -> impl Iterator<Item = &TodoItem> {
+        self.items().filter(move |item| item.parent == Some(idx))
+    }
+
+    /// The parent of the item, its parent and so on up to the top.
+    pub fn ancestors(&self, idx: Index) -> Vec<Index> {
+        let mut chain = vec![];
+        let mut current = self.get(idx).ok().and_then(|item| item.parent);
+        while let Some(p) = current {
+            // A parent chain can't be longer than the list, unless a
+            // hand-edited snapshot made a loop.
+            if chain.len() == self.items.len() {
+                break;
+            }
+            chain.push(p);
+            current = self.get(p).ok().and_then(|item| item.parent);
+        }
+        chain
     }
 
     pub fn push(&mut self, description: Description, tags: Vec<Tag>) -> Index {
-        let words = description
-            .value()
-            .split(' ')
-            .map(|x| x.to_owned())
-            .collect();
-        let tags = tags.iter().map(|x| x.value().to_owned()).collect();
-        let words_hash = hash_words(&words);
-        let tags_hash = hash_words(&tags);
+        self.insert(description, tags, Attributes::default())
+    }
 
-        self.items.push(TodoItem::new(
-            Index(self.top_index.value()),
-            words,
-            tags,
-            false,
-            words_hash,
-            tags_hash,
-        ));
+    /// Adds an item; its parent, if it has one, has to exist.
+    pub fn add(
+        &mut self,
+        description: Description,
+        tags: Vec<Tag>,
+        attributes: Attributes,
+    ) -> Result<Index, QueryError> {
+        let attributes = attributes.resolve(self.today())?;
+        self.check_parent(None, attributes.parent)?;
+        self.check_dependencies(None, &attributes.after)?;
+        Ok(self.insert(description, tags, attributes))
+    }
+
+    /// Makes sure `parent` can be the parent of `idx`, or of a new item.
+    fn check_parent(&self, idx: Option<Index>, parent: Option<Index>) -> Result<(), QueryError> {
+        let parent = match parent {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        self.get(parent)?;
+        match idx {
+            Some(i) if i == parent || self.ancestors(parent).contains(&i) => {
+                Err(QueryError::ParentCycle(i))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Makes sure `idx`, or a new item, can wait for each of `after`
+    /// without ending up waiting for itself.
+    fn check_dependencies(&self, idx: Option<Index>, after: &[Index]) -> Result<(), QueryError> {
+        for &dep in after {
+            self.get(dep)?;
+            if let Some(i) = idx {
+                if self.waits_for(dep, i) {
+                    return Err(QueryError::DependencyCycle(i));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `idx` is `other` or waits for it, directly or not.
+    fn waits_for(&self, idx: Index, other: Index) -> bool {
+        let mut seen = vec![false; self.items.len()];
+        let mut todo = vec![idx];
+        while let Some(i) = todo.pop() {
+            if i == other {
+                return true;
+            }
+            match self.get(i) {
+                Ok(item) if !seen[i.value() as usize] => {
+                    seen[i.value() as usize] = true;
+                    todo.extend(item.after.iter().copied());
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Whether any of the items the item waits for is still open.
+    pub fn is_blocked(&self, item: &TodoItem) -> bool {
+        item.after
+            .iter()
+            .any(|&dep| self.get(dep).is_ok_and(|d| !d.done))
+    }
+
+    /// Makes `blocked` wait for `blocker`.
+    pub fn block(&mut self, blocker: Index, blocked: Index) -> Result<Index, QueryError> {
+        self.get(blocked)?;
+        self.check_dependencies(Some(blocked), &[blocker])?;
+        let item = self.item_mut(blocked)?;
+        if !item.after.contains(&blocker) {
+            item.after.push(blocker);
+        }
+        Ok(blocked)
+    }
+
+    fn insert(
+        &mut self,
+        description: Description,
+        tags: Vec<Tag>,
+        attributes: Attributes,
+    ) -> Index {
+        let mut item = TodoItem::create(self.top_index, &description, &tags, false);
+        item.set_attributes(&attributes, self.today());
+        item.created_at = Some(self.now());
+        self.items.push(Some(item));
         let v = self.top_index;
         self.top_index.increment();
         v
     }
 
-    pub fn done_with_index(&mut self, idx: Index) -> Option<Index> {
-        if idx.value() < self.top_index.value() {
-            self.items[idx.value() as usize].done = true;
-            Some(idx)
-        } else {
-            None
+    fn item_mut(&mut self, idx: Index) -> Result<&mut TodoItem, QueryError> {
+        match self.items.get_mut(idx.value() as usize) {
+            None => Err(QueryError::InvalidIndex(idx)),
+            Some(None) => Err(QueryError::Deleted(idx)),
+            Some(Some(item)) => Ok(item),
+        }
+    }
+
+    /// Deletes the item; its subtasks move up to its own parent, and the
+    /// items that waited for it no longer do.
+    pub fn delete(&mut self, idx: Index) -> Result<Index, QueryError> {
+        self.take(idx).map(|_| idx)
+    }
+
+    /// Deletes the item like `delete`, handing it back.
+    pub fn take(&mut self, idx: Index) -> Result<TodoItem, QueryError> {
+        self.item_mut(idx)?;
+        let taken = self.items[idx.value() as usize].take();
+        let taken = taken.expect("item_mut found the item");
+        for item in self.items.iter_mut().flatten() {
+            if item.parent == Some(idx) {
+                item.parent = taken.parent;
+            }
+            item.after.retain(|&dep| dep != idx);
         }
+        Ok(taken)
     }
 
-    pub fn search(&self, sp: SearchParams) -> Vec<Index> {
-        let s_words: Vec<String> = sp.words.iter().map(|x| x.value().to_owned()).collect();
-        let s_tags: Vec<String> = sp.tags.iter().map(|x| x.value().to_owned()).collect();
+    /// Adds an item taken from another list under a new index. Links to
+    /// other items are dropped, since those indices meant the other list.
+    pub fn adopt(&mut self, mut item: TodoItem) -> Index {
+        item.index = self.top_index;
+        item.parent = None;
+        item.after = vec![];
+        item.series = None;
+        item.next = None;
+        self.items.push(Some(item));
+        let v = self.top_index;
+        self.top_index.increment();
+        v
+    }
 
-        self.items.par_iter().rev().filter(|item|
-            !item.done
-                && match_words(&s_words, &item.words_hash, &item.description)
-                && match_words(&s_tags, &item.tags_hash, &item.tags)
+    /// Marks the item done. The first time a recurring item is done, its
+    /// next occurrence is added, due on the first day of the series that is
+    /// both after its due date and after today.
+    ///
+    /// Unless `set_subtasks_block_done(false)` was called, an item with open
+    /// subtasks can't be done.
+    pub fn done_with_index(&mut self, idx: Index) -> Result<Index, QueryError> {
+        if self.subtasks_block_done && self.children(idx).any(|child| !child.done) {
+            self.get(idx)?;
+            return Err(QueryError::OpenSubtasks(idx));
+        }
+        let now = self.now();
+        let next_index = self.top_index;
+        let item = self.item_mut(idx)?;
+        if item.done {
+            return Err(QueryError::AlreadyDone(idx));
+        }
+        item.done = true;
+        item.completed_at = Some(now);
+
+        let rule = match (item.recurrence, item.next) {
+            (Some(rule), None) => rule,
+            _ => return Ok(idx),
+        };
+        let mut due = rule.after(item.due.unwrap_or_else(|| now.date()));
+        while due <= now.date() {
+            due = rule.after(due);
+        }
+        if !due.is_writable() {
+            // The series ends with the last date the journal can hold.
+            return Ok(idx);
+        }
+        let mut next = TodoItem::create(next_index, &item.description(), &item.tags(), false);
+        next.set_attributes(&item.attributes(), now.date());
+        next.due = Some(due);
+        next.recurrence = Some(rule.anchored(next.due));
+        next.series = Some(item.series.unwrap_or(idx));
+        next.created_at = Some(now);
+        item.next = Some(next_index);
 
-        ).map(|item| item.index).collect()
+        self.items.push(Some(next));
+        self.top_index.increment();
+        Ok(idx)
     }
-}
 
+    pub fn undone_with_index(&mut self, idx: Index) -> Result<Index, QueryError> {
+        let item = self.item_mut(idx)?;
+        if !item.done {
+            return Err(QueryError::NotDone(idx));
+        }
+        item.done = false;
+        item.completed_at = None;
+        Ok(idx)
+    }
+
+    /// Applies `edit` to the item. `Edit::All` replaces the description and
+    /// tags; of the other attributes it changes those it gives or clears,
+    /// and keeps the rest.
+    pub fn edit(&mut self, idx: Index, edit: Edit) -> Result<Index, QueryError> {
+        let edit = match edit {
+            Edit::All(d, ts, attrs) => {
+                let item = self.get(idx)?;
+                let attrs = attrs.resolve(self.today())?;
+                let after = if attrs.after.is_empty() && !attrs.clears(AttributeKind::After) {
+                    item.after.clone()
+                } else {
+                    attrs.after.clone()
+                };
+                let attrs = Attributes {
+                    priority: edited(
+                        &attrs,
+                        AttributeKind::Priority,
+                        attrs.priority,
+                        item.priority,
+                    ),
+                    due: edited(
+                        &attrs,
+                        AttributeKind::Due,
+                        attrs.due,
+                        item.due.map(DateExpr::On),
+                    ),
+                    recurrence: edited(
+                        &attrs,
+                        AttributeKind::Recurrence,
+                        attrs.recurrence,
+                        item.recurrence,
+                    ),
+                    parent: edited(&attrs, AttributeKind::Parent, attrs.parent, item.parent),
+                    after,
+                    cleared: vec![],
+                };
+                Edit::All(d, ts, attrs)
+            }
+            edit => edit,
+        };
+        if let Edit::All(_, _, attrs) = &edit {
+            self.check_parent(Some(idx), attrs.parent)?;
+            self.check_dependencies(Some(idx), &attrs.after)?;
+        }
+        let today = self.today();
+        let item = self.item_mut(idx)?;
+        match edit {
+            Edit::Description(d) => item.set_description(&d),
+            Edit::Tags(ts) => item.set_tags(&ts),
+            Edit::Priority(p) => item.priority = p,
+            Edit::All(d, ts, attrs) => {
+                item.set_description(&d);
+                item.set_tags(&ts);
+                item.set_attributes(&attrs, today);
+            }
+        }
+        Ok(idx)
+    }
+
+    /// Finds the open items matching `sp`, newest first; with `sp.rank` the
+    /// best scoring ones come first instead, ties still newest first. With
+    /// `sp.by_priority` the results are ordered by priority before that.
+    pub fn search(&self, sp: SearchParams) -> Vec<SearchResult> {
+        let today = self.today();
+        let mut results: Vec<SearchResult> = self
+            .items
+            .par_iter()
+            .rev()
+            .filter_map(Option::as_ref)
+            .filter(|item| {
+                sp.status.admits(item.done) && match_expr(sp.mode, &sp.expr, item, self, today)
+            })
+            .map(|item| {
+                let mut r = SearchResult::from_item(item);
+                let mut word_matches = vec![vec![]; item.description.len()];
+                r.tag_matches = vec![vec![]; item.tags.len()];
+                collect_matches(
+                    sp.mode,
+                    &sp.expr,
+                    item,
+                    &mut word_matches,
+                    &mut r.tag_matches,
+                );
+                r.description_matches = tokenize(&item.text)
+                    .into_iter()
+                    .zip(word_matches)
+                    .flat_map(|((start, _), ps)| ps.into_iter().map(move |p| start + p))
+                    .collect();
+                if sp.rank {
+                    r.score = Some(score_expr(sp.mode, &sp.expr, item));
+                }
+                r.parents = self.ancestors(item.index);
+                r.parents.reverse();
+                r
+            })
+            .collect();
+        if sp.rank {
+            results.sort_by_key(|r| Reverse(r.score));
+        }
+        if sp.by_priority {
+            results.sort_by_key(|r| Reverse(r.attributes.priority));
+        }
+        results
+    }
+
+    /// Every tag in use with how many open and done items carry it, sorted
+    /// by tag.
+    pub fn tag_summary(&self) -> Vec<TagSummary> {
+        // By folded tag, with the first spelling seen.
+        let mut counts: BTreeMap<String, (&str, usize, usize)> = BTreeMap::new();
+        for item in self.items() {
+            let mut seen: Vec<String> = vec![];
+            for t in &item.tags {
+                let key = fold_str(t);
+                if seen.contains(&key) {
+                    continue;
+                }
+                let (_, open, done) = counts.entry(key.clone()).or_insert((t, 0, 0));
+                if item.done {
+                    *done += 1;
+                } else {
+                    *open += 1;
+                }
+                seen.push(key);
+            }
+        }
+        counts
+            .into_values()
+            .map(|(t, open, done)| TagSummary {
+                tag: Tag::new(t),
+                open,
+                done,
+            })
+            .collect()
+    }
+
+    /// Replaces each of the tags in `from`, and the tags below them, with
+    /// `to` on every item, returning how many items changed. Each tag in
+    /// `from` has to be in use. Tags compare ignoring case, as in searches.
+    pub fn rename_tags(&mut self, from: &[Tag], to: &Tag) -> Result<usize, QueryError> {
+        // What is left of `t` below `f`, if `t` is `f` or below it.
+        let below = |t: &str, f: &Tag| -> Option<String> {
+            let mut rest = t.chars();
+            let prefix = f
+                .value()
+                .chars()
+                .all(|c| rest.next().map(fold) == Some(fold(c)));
+            Some(rest.as_str())
+                .filter(|rest| prefix && (rest.is_empty() || rest.starts_with('/')))
+                .map(str::to_owned)
+        };
+        let renamed = |t: &str| -> Option<String> {
+            from.iter()
+                .find_map(|f| below(t, f))
+                .map(|rest| format!("{}{}", to, rest))
+        };
+        for f in from {
+            let in_use = self
+                .items()
+                .any(|item| item.tags.iter().any(|t| below(t, f).is_some()));
+            if !in_use {
+                return Err(QueryError::UnknownTag(f.clone()));
+            }
+        }
+        let mut changed = 0;
+        for item in self.items.iter_mut().flatten() {
+            if !item.tags.iter().any(|t| renamed(t).is_some()) {
+                continue;
+            }
+            let mut tags: Vec<Tag> = vec![];
+            for t in &item.tags {
+                let t = Tag::new(&renamed(t).unwrap_or_else(|| t.clone()));
+                if !tags.iter().any(|x| same_tag(x, &t)) {
+                    tags.push(t);
+                }
+            }
+            item.set_tags(&tags);
+            changed += 1;
+        }
+        Ok(changed)
+    }
+
+    /// Adds and removes tags of one item, in order. Removing a tag the item
+    /// does not have is an error, adding one it has already does nothing;
+    /// tags compare ignoring case.
+    pub fn retag(&mut self, idx: Index, changes: &[TagChange]) -> Result<Index, QueryError> {
+        let item = self.item_mut(idx)?;
+        let mut tags = item.tags();
+        for change in changes {
+            match change {
+                TagChange::Add(t) if !tags.iter().any(|x| same_tag(x, t)) => {
+                    tags.push(t.clone())
+                }
+                TagChange::Add(_) => {}
+                TagChange::Remove(t) => match tags.iter().position(|x| same_tag(x, t)) {
+                    Some(i) => {
+                        tags.remove(i);
+                    }
+                    None => return Err(QueryError::NotTagged(idx, t.clone())),
+                },
+            }
+        }
+        item.set_tags(&tags);
+        Ok(idx)
+    }
+
+    /// The tags in use, as a tree of paths sorted by name: `work/backend`
+    /// shows up as `backend` below `work`, whether or not any item is
+    /// tagged with just `work`.
+    pub fn tag_tree(&self) -> Vec<TagNode> {
+        // By folded path, with the first spelling seen, as in `tag_summary`.
+        let mut counts: BTreeMap<String, (Tag, usize)> = BTreeMap::new();
+        for item in self.items() {
+            let mut paths: Vec<(String, Tag)> = item
+                .tags()
+                .iter()
+                .flat_map(Tag::ancestors_and_self)
+                .map(|p| (fold_str(p.value()), p))
+                .collect();
+            paths.sort_by(|a, b| a.0.cmp(&b.0));
+            paths.dedup_by(|a, b| a.0 == b.0);
+            for (key, p) in paths {
+                counts.entry(key).or_insert((p, 0)).1 += 1;
+            }
+        }
+        fn below(counts: &BTreeMap<String, (Tag, usize)>, parent: Option<&str>) -> Vec<TagNode> {
+            counts
+                .iter()
+                .filter(|(path, _)| match parent {
+                    Some(p) => path
+                        .strip_prefix(p)
+                        .and_then(|rest| rest.strip_prefix('/'))
+                        .is_some_and(|rest| !rest.contains('/')),
+                    None => !path.contains('/'),
+                })
+                .map(|(path, (tag, items))| TagNode {
+                    tag: tag.clone(),
+                    items: *items,
+                    children: below(counts, Some(path)),
+                })
+                .collect()
+        }
+        below(&counts, None)
+    }
+
+    /// The items with `status`, each followed by its subtasks, with how
+    /// deep they are nested. Parents are listed whatever their status, so
+    /// that every item shows up under its parent.
+    pub fn tree(&self, status: StatusFilter) -> Vec<(usize, SearchResult)> {
+        let mut shown = vec![false; self.items.len()];
+        for item in self.items().filter(|item| status.admits(item.done)) {
+            shown[item.index.value() as usize] = true;
+            for p in self.ancestors(item.index) {
+                shown[p.value() as usize] = true;
+            }
+        }
+        let mut children: Vec<Vec<&TodoItem>> = vec![vec![]; self.items.len()];
+        let mut roots = vec![];
+        for item in self.items().filter(|item| shown[item.index.value() as usize]) {
+            match item.parent {
+                Some(p) if self.get(p).is_ok() => children[p.value() as usize].push(item),
+                _ => roots.push(item),
+            }
+        }
+
+        let mut lines = vec![];
+        let mut stack: Vec<(usize, &TodoItem)> = roots.into_iter().rev().map(|i| (0, i)).collect();
+        while let Some((depth, item)) = stack.pop() {
+            let mut r = SearchResult::from_item(item);
+            r.parents = vec![];
+            lines.push((depth, r));
+            let below = &children[item.index.value() as usize];
+            stack.extend(below.iter().rev().map(|child| (depth + 1, *child)));
+        }
+        lines
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
     
     #[test]
-    fn test_get_alphabet_position() {
-        assert_eq!(get_alphabet_position('a' as u8), 0);
-        assert_eq!(get_alphabet_position('A' as u8), 0);
-        assert_eq!(get_alphabet_position('z' as u8), 25);
-        assert_eq!(get_alphabet_position('Z' as u8), 25);
+    fn test_fold() {
+        assert_eq!(fold('a'), 'a');
+        assert_eq!(fold('A'), 'a');
+        assert_eq!(fold('É'), 'é');
+        assert_eq!(fold('Σ'), 'σ');
+        assert_eq!(fold_str("Café Q3"), "café q3");
     }
 
     #[test]
     fn test_get_bit_position() {
-        assert_eq!(get_bit_position('a' as u8), 1 << 0);
-        assert_eq!(get_bit_position('A' as u8), 1 << 0);
-        assert_eq!(get_bit_position('z' as u8), 1 << 25);
-        assert_eq!(get_bit_position('Z' as u8), 1 << 25);
+        assert_eq!(get_bit_position('a'), 1 << 0);
+        assert_eq!(get_bit_position('A'), 1 << 0);
+        assert_eq!(get_bit_position('z'), 1 << 25);
+        assert_eq!(get_bit_position('Z'), 1 << 25);
+        assert_eq!(get_bit_position('3'), 1 << 26);
+        assert_eq!(get_bit_position('É'), get_bit_position('é'));
+        assert_ne!(get_bit_position('é'), 0);
     }
 
     #[test]
@@ -280,43 +1396,43 @@ mod tests {
 
     #[test]
     fn test_is_subsequence() {
-        assert_eq!(is_subsequence(&String::from("abc"), &String::from("abcd")), true);
-        assert_eq!(is_subsequence(&String::from("abc"), &String::from("ab")), false);
-        assert_eq!(is_subsequence(&String::from("abc"), &String::from("acb")), false);
-        assert_eq!(is_subsequence(&String::from("abc"), &String::from("acd")), false);
-        assert_eq!(is_subsequence(&String::from("abc"), &String::from("abc")), true);
-        assert_eq!(is_subsequence(&String::from("groceries"), &String::from("groceries")), true);
-        assert_eq!(is_subsequence(&String::from(""), &String::from("abc")), true);
-        assert_eq!(is_subsequence(&String::from("bxy"), &String::from("abc")), false);
+        assert!(is_subsequence(&String::from("abc"), &String::from("abcd")));
+        assert!(!is_subsequence(&String::from("abc"), &String::from("ab")));
+        assert!(!is_subsequence(&String::from("abc"), &String::from("acb")));
+        assert!(!is_subsequence(&String::from("abc"), &String::from("acd")));
+        assert!(is_subsequence(&String::from("abc"), &String::from("abc")));
+        assert!(is_subsequence(&String::from("groceries"), &String::from("groceries")));
+        assert!(is_subsequence("", &String::from("abc")));
+        assert!(!is_subsequence(&String::from("bxy"), &String::from("abc")));
         
-        assert_eq!(is_subsequence(&String::from("-"), &String::from("ab-c")), true);
-        assert_eq!(is_subsequence(&String::from("-"), &String::from("abc")), false);
+        assert!(is_subsequence(&String::from("-"), &String::from("ab-c")));
+        assert!(!is_subsequence(&String::from("-"), &String::from("abc")));
     } 
 
     #[test]
     fn test_match_with_hash() {
         // true positive
-        assert_eq!(match_with_hash(&String::from("abc"), &vec![0b1, 0b10, 0b100]), true);
+        assert!(match_with_hash(&String::from("abc"), &[0b1, 0b10, 0b100]));
         // true negative
-        assert_eq!(match_with_hash(&String::from("abc"), &vec![0b1, 0b10, 0b1]), false);
+        assert!(!match_with_hash(&String::from("abc"), &[0b1, 0b10, 0b1]));
         // false positive, "aac" & "bbb"
-        assert_eq!(match_with_hash(&String::from("abc"), &vec![0b11, 0b11, 0b110]), true);
+        assert!(match_with_hash(&String::from("abc"), &[0b11, 0b11, 0b110]));
     }
 
     #[test]
     fn test_match_deterministic() {
         let mut words = vec![String::from("abc"), String::from("acd")];
-        assert_eq!(match_word_deterministic(&String::from("abc"), &words), true);
-        assert_eq!(match_word_deterministic(&String::from("acd"), &words), true);
-        assert_eq!(match_word_deterministic(&String::from("abd"), &words), false);
-        assert_eq!(match_word_deterministic(&String::from("groceries"), &words), false);
+        assert!(match_word_deterministic(&String::from("abc"), &words));
+        assert!(match_word_deterministic(&String::from("acd"), &words));
+        assert!(!match_word_deterministic(&String::from("abd"), &words));
+        assert!(!match_word_deterministic(&String::from("groceries"), &words));
 
         words.push(String::from("groceries"));
-        assert_eq!(match_word_deterministic(&String::from("groceries"), &words), true);
-        assert_eq!(match_word_deterministic(&String::from("g"), &words), true);
+        assert!(match_word_deterministic(&String::from("groceries"), &words));
+        assert!(match_word_deterministic(&String::from("g"), &words));
 
         let empty: Vec<String> = vec![];
-        assert_eq!(match_word_deterministic(&String::from("anything"), &empty), false);
+        assert!(!match_word_deterministic(&String::from("anything"), &empty));
     }
 
     #[test]
@@ -326,15 +1442,808 @@ mod tests {
         let mut words_hash = vec![];
 
         // Empty patterns case
-        assert_eq!(match_words(&patterns, &words_hash, &words), true);
+        assert!(match_words(MatchMode::Subsequence, &patterns, &words_hash, &words));
         words.push(String::from("abc"));
         words_hash.push(0b11);
-        assert_eq!(match_words(&patterns, &words_hash, &words), true);
+        assert!(match_words(MatchMode::Subsequence, &patterns, &words_hash, &words));
 
         let g = String::from("groceries");
         patterns.push(g.clone());
         words.push(g.clone());
         words_hash = hash_words(&words);
-        assert_eq!(match_words(&patterns, &words_hash, &words), true);
+        assert!(match_words(MatchMode::Subsequence, &patterns, &words_hash, &words));
+    }
+
+    #[test]
+    fn test_search_returns_item_details() {
+        let mut tl = TodoList::new();
+        tl.push(Description::new("buy bread"), Tag::from_strings(vec!["groceries"]));
+        tl.push(Description::new("buy milk"), Tag::from_strings(vec!["groceries", "dairy"]));
+
+        let found = tl.search(SearchParams {
+            expr: SearchExpr::Word(SearchWord::new("milk")),
+            mode: MatchMode::Subsequence,
+            status: StatusFilter::Open,
+            rank: false,
+            by_priority: false,
+            all_lists: false,
+        });
+        assert_eq!(
+            found,
+            vec![SearchResult {
+                list: None,
+                index: Index::new(1),
+                description: Description::new("buy milk"),
+                tags: Tag::from_strings(vec!["groceries", "dairy"]),
+                done: false,
+                attributes: Attributes::default(),
+                parents: vec![],
+                score: None,
+                description_matches: vec![4, 5, 6, 7],
+                tag_matches: vec![vec![], vec![]],
+            }]
+        );
+        assert_eq!(found[0].to_string(), "1 \"buy milk\" #groceries #dairy");
+    }
+
+    #[test]
+    fn test_search_boolean_expressions() {
+        let mut tl = TodoList::new();
+        tl.push(Description::new("buy bread"), Tag::from_strings(vec!["groceries"]));
+        tl.push(Description::new("buy milk"), Tag::from_strings(vec!["groceries", "work"]));
+        tl.push(Description::new("call parents"), Tag::from_strings(vec!["relatives"]));
+
+        assert_eq!(found(&tl, "search buy or call"), vec![2, 1, 0]);
+        assert_eq!(found(&tl, "search buy or call not #work"), vec![2, 0]);
+        assert_eq!(found(&tl, "search buy or call -#groceries"), vec![2]);
+        assert_eq!(found(&tl, "search not (milk or parents)"), vec![0]);
+        assert_eq!(found(&tl, "search buy and not bread"), vec![1]);
+        assert_eq!(found(&tl, "search (buy bread) or parents"), vec![2, 0]);
+        assert_eq!(found(&tl, "search"), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_match_with_hash_in_mode() {
+        let words_hash = hash_words(&[String::from("abc"), String::from("bcd")]);
+        // prefixes have to line up with the start of a word
+        assert!(match_with_hash_in_mode(MatchMode::Prefix, "bc", &words_hash));
+        assert!(!match_with_hash_in_mode(MatchMode::Prefix, "cd", &words_hash));
+        assert!(!match_with_hash_in_mode(MatchMode::Exact, "abcd", &words_hash));
+        // substrings may start anywhere, but must be contiguous
+        assert!(match_with_hash_in_mode(MatchMode::Substring, "cd", &words_hash));
+        assert!(!match_with_hash_in_mode(MatchMode::Substring, "db", &words_hash));
+        assert!(match_with_hash_in_mode(MatchMode::Substring, "", &words_hash));
+        assert!(match_with_hash_in_mode(MatchMode::Subsequence, "ac", &words_hash));
+    }
+
+    #[test]
+    fn test_match_word_in_mode() {
+        let words = vec![String::from("groceries"), String::from("bread")];
+        assert!(match_word_in_mode(MatchMode::Subsequence, "gre", &words));
+        assert!(!match_word_in_mode(MatchMode::Prefix, "gre", &words));
+        assert!(match_word_in_mode(MatchMode::Prefix, "groc", &words));
+        assert!(match_word_in_mode(MatchMode::Substring, "rie", &words));
+        assert!(!match_word_in_mode(MatchMode::Substring, "gce", &words));
+        assert!(match_word_in_mode(MatchMode::Exact, "bread", &words));
+        assert!(!match_word_in_mode(MatchMode::Exact, "brea", &words));
+    }
+
+    #[test]
+    fn test_match_positions() {
+        let sub = MatchMode::Subsequence;
+        assert_eq!(match_positions(sub, "ab", "axab"), Some(vec![2, 3]));
+        assert_eq!(match_positions(sub, "ac", "abc"), Some(vec![0, 2]));
+        assert_eq!(match_positions(sub, "ca", "abc"), None);
+        assert_eq!(match_positions(sub, "", "abc"), Some(vec![]));
+        assert_eq!(
+            match_positions(MatchMode::Substring, "ro", "groceries"),
+            Some(vec![1, 2])
+        );
+        assert_eq!(match_positions(MatchMode::Prefix, "ro", "groceries"), None);
+        assert_eq!(
+            match_positions(MatchMode::Exact, "Milk", "milk"),
+            Some(vec![0, 1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn test_ranked_search() {
+        let mut tl = TodoList::new();
+        for (d, ts) in [
+            ("smoke", vec![]),
+            ("mkdir", vec![]),
+            ("milk", vec![]),
+            ("mk", vec![]),
+            ("remark", vec!["mk"]),
+            ("milky", vec![]),
+        ] {
+            tl.push(Description::new(d), Tag::from_strings(ts));
+        }
+
+        let ranked = |line: &str| -> Vec<(u64, Option<u32>)> {
+            match parser::parse_line(1, line) {
+                Ok(Query::Search(sp)) => tl
+                    .search(sp)
+                    .iter()
+                    .map(|r| (r.index.value(), r.score))
+                    .collect(),
+                other => panic!("not a search: {:?}", other),
+            }
+        };
+        let order = |line: &str| -> Vec<u64> { ranked(line).iter().map(|r| r.0).collect() };
+
+        assert_eq!(order("search mk"), vec![5, 4, 3, 2, 1, 0]);
+        // exact word, then adjacent letters at the word start, then the rest
+        assert_eq!(order("search --rank mk"), vec![3, 1, 5, 2, 4, 0]);
+        assert_eq!(order("search --rank milk"), vec![2, 5]);
+        // a matching tag adds to the score
+        assert_eq!(order("search --rank mk or #mk"), vec![4, 3, 1, 5, 2, 0]);
+        assert!(ranked("search mk").iter().all(|r| r.1.is_none()));
+        assert_eq!(ranked("search --rank mk")[0], (3, Some(90)));
+    }
+
+    #[test]
+    fn test_search_reports_matched_positions() {
+        let mut tl = TodoList::new();
+        tl.push(
+            Description::new("buy bread"),
+            Tag::from_strings(vec!["groceries", "bakery"]),
+        );
+
+        let sp = match parser::parse_line(1, "search br bd #gr not #work").unwrap() {
+            Query::Search(sp) => sp,
+            q => panic!("not a search: {:?}", q),
+        };
+        let found = tl.search(sp);
+        assert_eq!(found[0].description_matches, vec![4, 5, 8]);
+        assert_eq!(found[0].tag_matches, vec![vec![0, 1], vec![]]);
+    }
+
+    #[test]
+    fn test_edit_updates_search() {
+        let mut tl = TodoList::new();
+        tl.push(Description::new("buy mlik"), Tag::from_strings(vec!["groceries"]));
+        let find = |tl: &TodoList, line: &str| -> usize {
+            match parser::parse_line(1, line).unwrap() {
+                Query::Search(sp) => tl.search(sp).len(),
+                q => panic!("not a search: {:?}", q),
+            }
+        };
+
+        let fixed = Edit::Description(Description::new("buy milk"));
+        assert_eq!(tl.edit(Index::new(0), fixed), Ok(Index::new(0)));
+        assert_eq!(find(&tl, "search --exact milk #groceries"), 1);
+
+        tl.edit(Index::new(0), Edit::Tags(Tag::from_strings(vec!["dairy"])))
+            .unwrap();
+        assert_eq!(find(&tl, "search milk #groceries"), 0);
+        assert_eq!(find(&tl, "search milk #dairy"), 1);
+
+        tl.edit(
+            Index::new(0),
+            Edit::All(
+                Description::new("call parents"),
+                vec![],
+                Attributes::default(),
+            ),
+        )
+        .unwrap();
+        assert_eq!(find(&tl, "search milk"), 0);
+        assert_eq!(find(&tl, "search call"), 1);
+        assert_eq!(tl.get(Index::new(0)).unwrap().tags, Vec::<String>::new());
+
+        assert_eq!(
+            tl.edit(Index::new(1), Edit::Tags(vec![])),
+            Err(QueryError::InvalidIndex(Index::new(1)))
+        );
+    }
+
+    #[test]
+    fn test_done_and_undone() {
+        let mut tl = TodoList::new();
+        let i = tl.push(Description::new("buy milk"), vec![]);
+        let open = |tl: &TodoList| tl.search(parser_search("search")).len();
+
+        assert_eq!(tl.undone_with_index(i), Err(QueryError::NotDone(i)));
+        assert_eq!(tl.done_with_index(i), Ok(i));
+        assert_eq!(open(&tl), 0);
+        assert_eq!(tl.done_with_index(i), Err(QueryError::AlreadyDone(i)));
+        assert_eq!(tl.undone_with_index(i), Ok(i));
+        assert_eq!(open(&tl), 1);
+
+        let missing = Index::new(1);
+        assert_eq!(
+            tl.done_with_index(missing),
+            Err(QueryError::InvalidIndex(missing))
+        );
+        assert_eq!(
+            tl.undone_with_index(missing),
+            Err(QueryError::InvalidIndex(missing))
+        );
+    }
+
+    #[test]
+    fn test_search_by_status() {
+        let mut tl = TodoList::new();
+        let bread = tl.push(Description::new("buy bread"), vec![]);
+        let milk = tl.push(Description::new("buy milk"), vec![]);
+        tl.done_with_index(bread).unwrap();
+
+        let found = |line: &str| -> Vec<(Index, bool)> {
+            tl.search(parser_search(line))
+                .iter()
+                .map(|r| (r.index, r.done))
+                .collect()
+        };
+        assert_eq!(found("search buy"), vec![(milk, false)]);
+        assert_eq!(found("search --all buy"), vec![(milk, false), (bread, true)]);
+        assert_eq!(found("search --done-only buy"), vec![(bread, true)]);
+    }
+
+    #[test]
+    fn test_unicode_search() {
+        let mut tl = TodoList::new();
+        let cafe = tl.push(
+            Description::new("Café mit Bob"),
+            Tag::from_strings(vec!["Q3", "Straße"]),
+        );
+        tl.push(Description::new("call bob"), vec![]);
+
+        assert_eq!(found(&tl, "search CAFÉ"), vec![cafe.value()]);
+        assert_eq!(found(&tl, "search --exact bob"), vec![1, 0]);
+        assert_eq!(found(&tl, "search #q3 #straße"), vec![0]);
+        assert_eq!(found(&tl, "search --prefix #STR"), vec![0]);
+        assert_eq!(found(&tl, "search cafe"), Vec::<u64>::new());
+        assert_eq!(
+            tl.search(parser_search("search --substring AFÉ"))[0].description_matches,
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("email: re q3 (urgent), 2pm"),
+            vec![(0, "email"), (7, "re"), (10, "q3"), (14, "urgent"), (23, "2pm")]
+        );
+        assert_eq!(tokenize("café à la-carte"), vec![(0, "café"), (5, "à"), (7, "la-carte")]);
+        assert_eq!(tokenize(" ... "), Vec::<(usize, &str)>::new());
+    }
+
+    #[test]
+    fn test_search_punctuated_description() {
+        let mut tl = TodoList::new();
+        tl.push(Description::new("email: re q3 (urgent), 2pm"), vec![]);
+
+        let found = tl.search(parser_search("search --exact urgent 2pm"));
+        assert_eq!(found[0].description_matches, vec![14, 15, 16, 17, 18, 19, 23, 24, 25]);
+        assert_eq!(
+            found[0].render(Highlight::Brackets),
+            "0 \"email: re q3 ([urgent]), [2pm]\""
+        );
+    }
+
+    #[test]
+    fn test_search_by_priority() {
+        let mut tl = TodoList::new();
+        let prio = |p| Attributes {
+            priority: Some(p),
+            ..Attributes::default()
+        };
+        tl.add(Description::new("fix login"), vec![], prio(Priority::High)).unwrap();
+        tl.add(Description::new("fix typo"), vec![], prio(Priority::Low)).unwrap();
+        tl.push(Description::new("fix docs"), vec![]);
+        tl.add(Description::new("fix tests"), vec![], prio(Priority::Medium)).unwrap();
+        tl.add(Description::new("fix build"), vec![], prio(Priority::High)).unwrap();
+
+        assert_eq!(found(&tl, "search fix"), vec![4, 3, 2, 1, 0]);
+        assert_eq!(found(&tl, "search --by-priority fix"), vec![4, 0, 3, 1, 2]);
+        assert_eq!(found(&tl, "search priority>=medium"), vec![4, 3, 0]);
+        assert_eq!(found(&tl, "search priority<medium"), vec![1]);
+        assert_eq!(found(&tl, "search not priority=high"), vec![3, 2, 1]);
+
+        tl.edit(Index::new(0), Edit::Priority(None)).unwrap();
+        assert_eq!(found(&tl, "search priority>low"), vec![4, 3]);
+    }
+
+    #[test]
+    fn test_search_by_due_date() {
+        let date = |s: &str| -> Date { s.parse().unwrap() };
+        let due = |s: &str| Attributes {
+            due: Some(DateExpr::On(date(s))),
+            ..Attributes::default()
+        };
+        let mut tl = TodoList::new();
+        tl.set_clock(Clock::Fixed(date("2026-10-15").into()));
+        tl.add(Description::new("pay rent"), vec![], due("2026-10-01")).unwrap();
+        tl.add(Description::new("file taxes"), vec![], due("2026-10-19")).unwrap();
+        tl.add(Description::new("renew passport"), vec![], due("2026-11-01")).unwrap();
+        tl.push(Description::new("read more"), vec![]);
+        tl.add(Description::new("call bank"), vec![], due("2026-10-12")).unwrap();
+        tl.done_with_index(Index::new(4)).unwrap();
+
+        assert_eq!(found(&tl, "search due<2026-11-01"), vec![1, 0]);
+        assert_eq!(found(&tl, "search due:2026-11-01"), vec![2]);
+        assert_eq!(found(&tl, "search due>=today"), vec![2, 1]);
+        assert_eq!(found(&tl, "search is:overdue"), vec![0]);
+        assert_eq!(found(&tl, "search --all is:overdue"), vec![0]);
+        assert_eq!(found(&tl, "search --all due:this-week"), vec![4]);
+
+        tl.set_clock(Clock::Fixed(date("2026-10-20").into()));
+        assert_eq!(found(&tl, "search is:overdue"), vec![1, 0]);
+        assert_eq!(found(&tl, "search --all due:this-week"), vec![1]);
+    }
+
+    #[test]
+    fn test_created_and_completed_times() {
+        let at = |s: &str| -> Timestamp { s.parse().unwrap() };
+        let mut tl = TodoList::new();
+        tl.set_clock(Clock::Fixed(at("2026-09-30T18:00:00Z")));
+        let rent = tl.push(Description::new("pay rent"), vec![]);
+        tl.set_clock(Clock::Fixed(at("2026-10-14T08:00:00Z")));
+        let taxes = tl.push(Description::new("file taxes"), vec![]);
+        let bank = tl.push(Description::new("call bank"), vec![]);
+        tl.set_clock(Clock::Fixed(at("2026-10-15T09:30:00Z")));
+        tl.done_with_index(rent).unwrap();
+        tl.done_with_index(bank).unwrap();
+
+        let item = tl.get(rent).unwrap();
+        assert_eq!(item.created_at, Some(at("2026-09-30T18:00:00Z")));
+        assert_eq!(item.completed_at, Some(at("2026-10-15T09:30:00Z")));
+        assert_eq!(tl.get(taxes).unwrap().completed_at, None);
+
+        assert_eq!(found(&tl, "search --all created>2026-10-01"), vec![2, 1]);
+        assert_eq!(found(&tl, "search --done-only completed:today"), vec![2, 0]);
+        assert_eq!(found(&tl, "search --all created:this-week"), vec![2, 1]);
+
+        tl.undone_with_index(bank).unwrap();
+        assert_eq!(tl.get(bank).unwrap().completed_at, None);
+        assert_eq!(found(&tl, "search --all completed:today"), vec![0]);
+    }
+
+    #[test]
+    fn test_recurring_items() {
+        let date = |s: &str| -> Date { s.parse().unwrap() };
+        let every = |r, due: Option<&str>| Attributes {
+            due: due.map(|d| DateExpr::On(date(d))),
+            recurrence: Some(r),
+            ..Attributes::default()
+        };
+        let mut tl = TodoList::new();
+        // a Thursday
+        tl.set_clock(Clock::Fixed(date("2026-10-15").into()));
+        let bins = tl.add(
+            Description::new("take out bins"),
+            Tag::from_strings(vec!["home"]),
+            every(Recurrence::Weeks(1), Some("2026-10-16")),
+        )
+        .unwrap();
+        let rent = tl.add(
+            Description::new("pay rent"),
+            vec![],
+            every(Recurrence::MonthlyOn(1), Some("2026-09-01")),
+        )
+        .unwrap();
+        let water = tl.add(
+            Description::new("water plants"),
+            vec![],
+            every(Recurrence::Days(2), None),
+        )
+        .unwrap();
+
+        tl.done_with_index(bins).unwrap();
+        let next = tl.get(Index::new(3)).unwrap();
+        assert_eq!(next.description(), Description::new("take out bins"));
+        assert_eq!(next.tags(), Tag::from_strings(vec!["home"]));
+        assert_eq!(next.due, Some(date("2026-10-23")));
+        assert_eq!(next.recurrence, Some(Recurrence::Weeks(1)));
+        assert_eq!((next.series, next.done), (Some(bins), false));
+        assert_eq!(tl.get(bins).unwrap().next, Some(Index::new(3)));
+
+        // Overdue occurrences are skipped.
+        tl.done_with_index(rent).unwrap();
+        assert_eq!(tl.get(Index::new(4)).unwrap().due, Some(date("2026-11-01")));
+        tl.done_with_index(water).unwrap();
+        assert_eq!(tl.get(Index::new(5)).unwrap().due, Some(date("2026-10-17")));
+
+        // The series stays linked to its first item.
+        tl.done_with_index(Index::new(3)).unwrap();
+        let third = tl.get(Index::new(6)).unwrap();
+        assert_eq!((third.series, third.due), (Some(bins), Some(date("2026-10-30"))));
+
+        // Doing an item again after `undone` does not add another occurrence.
+        tl.undone_with_index(bins).unwrap();
+        tl.done_with_index(bins).unwrap();
+        assert_eq!(tl.top_index(), Index::new(7));
+
+        // A monthly series keeps to its first day across short months, even
+        // once its first occurrence is gone.
+        let report = tl
+            .add(
+                Description::new("send report"),
+                vec![],
+                every(Recurrence::Monthly, Some("2027-01-31")),
+            )
+            .unwrap();
+        let anchored = Some(Recurrence::MonthlyOn(31));
+        assert_eq!(tl.get(report).unwrap().recurrence, anchored);
+        tl.done_with_index(report).unwrap();
+        let feb = tl.get(report).unwrap().next.unwrap();
+        assert_eq!(tl.get(feb).unwrap().due, Some(date("2027-02-28")));
+        assert_eq!(tl.get(feb).unwrap().recurrence, anchored);
+        tl.delete(report).unwrap();
+        tl.done_with_index(feb).unwrap();
+        let mar = tl.get(feb).unwrap().next.unwrap();
+        assert_eq!(tl.get(mar).unwrap().due, Some(date("2027-03-31")));
+        assert_eq!(tl.get(mar).unwrap().series, Some(report));
+
+        // Editing an occurrence keeps it recurring.
+        let edit = Edit::All(Description::new("send reports"), vec![], Attributes::default());
+        tl.edit(mar, edit).unwrap();
+        assert_eq!(tl.get(mar).unwrap().recurrence, anchored);
+
+        // There is no occurrence after 9999-12-31.
+        let last = tl
+            .add(
+                Description::new("renew lease"),
+                vec![],
+                every(Recurrence::Monthly, Some("9999-12-15")),
+            )
+            .unwrap();
+        tl.done_with_index(last).unwrap();
+        assert!(tl.get(last).unwrap().done);
+        assert_eq!(tl.get(last).unwrap().next, None);
+        assert_eq!(tl.top_index(), Index::new(11));
+    }
+
+    #[test]
+    fn test_subtasks() {
+        let mut tl = TodoList::new();
+        let under = |parent: u64| Attributes {
+            parent: Some(Index::new(parent)),
+            ..Attributes::default()
+        };
+        let release = tl.push(Description::new("release"), vec![]);
+        let tests = tl
+            .add(Description::new("write tests"), vec![], under(0))
+            .unwrap();
+        let unit = tl
+            .add(Description::new("unit tests"), vec![], under(1))
+            .unwrap();
+        tl.push(Description::new("lunch"), vec![]);
+        assert_eq!(
+            tl.add(Description::new("x"), vec![], under(9)),
+            Err(QueryError::InvalidIndex(Index::new(9)))
+        );
+        assert_eq!(tl.ancestors(unit), vec![tests, release]);
+        let found = tl.search(parser_search("search unit"));
+        assert_eq!(found[0].parents, vec![release, tests]);
+
+        let tree: Vec<(usize, u64)> = tl
+            .tree(StatusFilter::Open)
+            .into_iter()
+            .map(|(depth, r)| (depth, r.index.value()))
+            .collect();
+        assert_eq!(tree, vec![(0, 0), (1, 1), (2, 2), (0, 3)]);
+
+        // An item can't be done before its subtasks, nor nested under them.
+        assert_eq!(tl.done_with_index(tests), Err(QueryError::OpenSubtasks(tests)));
+        let edit = Edit::All(Description::new("release"), vec![], under(2));
+        assert_eq!(tl.edit(release, edit), Err(QueryError::ParentCycle(release)));
+        tl.done_with_index(unit).unwrap();
+        tl.done_with_index(tests).unwrap();
+
+        // Done parents are still listed above their open subtasks.
+        tl.undone_with_index(unit).unwrap();
+        let tree: Vec<(usize, u64)> = tl
+            .tree(StatusFilter::Open)
+            .into_iter()
+            .map(|(depth, r)| (depth, r.index.value()))
+            .collect();
+        assert_eq!(tree, vec![(0, 0), (1, 1), (2, 2), (0, 3)]);
+        assert_eq!(tl.tree(StatusFilter::Done).len(), 2);
+
+        tl.set_subtasks_block_done(false);
+        tl.done_with_index(release).unwrap();
+
+        // Deleting an item moves its subtasks up.
+        tl.delete(tests).unwrap();
+        assert_eq!(tl.get(unit).unwrap().parent, Some(release));
+
+        // Editing an item without `parent:` leaves it where it is.
+        let edit = Edit::All(Description::new("unit tests"), vec![], Attributes::default());
+        tl.edit(unit, edit).unwrap();
+        assert_eq!(tl.get(unit).unwrap().parent, Some(release));
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let mut tl = TodoList::new();
+        let review = tl.push(Description::new("review"), vec![]);
+        let tests = tl.push(Description::new("run tests"), vec![]);
+        let deploy = tl
+            .add(
+                Description::new("deploy"),
+                vec![],
+                Attributes {
+                    after: vec![review],
+                    ..Attributes::default()
+                },
+            )
+            .unwrap();
+        tl.block(tests, deploy).unwrap();
+        tl.block(review, tests).unwrap();
+        assert_eq!(tl.get(deploy).unwrap().after, vec![review, tests]);
+
+        // Nothing may end up waiting for itself.
+        assert_eq!(
+            tl.block(deploy, review),
+            Err(QueryError::DependencyCycle(review))
+        );
+        assert_eq!(
+            tl.block(deploy, deploy),
+            Err(QueryError::DependencyCycle(deploy))
+        );
+        let edit = Edit::All(
+            Description::new("review"),
+            vec![],
+            Attributes {
+                after: vec![tests],
+                ..Attributes::default()
+            },
+        );
+        assert_eq!(tl.edit(review, edit), Err(QueryError::DependencyCycle(review)));
+        assert_eq!(tl.block(Index::new(7), deploy), Err(QueryError::InvalidIndex(Index::new(7))));
+
+        assert_eq!(found(&tl, "search is:ready"), vec![0]);
+        assert_eq!(found(&tl, "search is:blocked"), vec![2, 1]);
+        tl.done_with_index(review).unwrap();
+        assert_eq!(found(&tl, "search is:ready"), vec![1]);
+        tl.delete(tests).unwrap();
+        assert_eq!(found(&tl, "search is:ready"), vec![2]);
+        assert_eq!(tl.get(deploy).unwrap().after, vec![review]);
+
+        // Editing an item keeps its dependencies unless `after:` is given.
+        let edit = |after: Vec<Index>| {
+            let attrs = Attributes {
+                after,
+                ..Attributes::default()
+            };
+            Edit::All(Description::new("deploy"), vec![], attrs)
+        };
+        tl.edit(deploy, edit(vec![])).unwrap();
+        assert_eq!(tl.get(deploy).unwrap().after, vec![review]);
+        let docs = tl.push(Description::new("write docs"), vec![]);
+        tl.edit(deploy, edit(vec![docs])).unwrap();
+        assert_eq!(tl.get(deploy).unwrap().after, vec![docs]);
+    }
+
+    #[test]
+    fn test_edit_keeps_or_clears_attributes() {
+        let mut tl = TodoList::new();
+        tl.set_clock(Clock::Fixed("2026-10-15T09:00:00Z".parse().unwrap()));
+        let run = |tl: &mut TodoList, line: &str| match parser::parse_line(1, line).unwrap() {
+            Query::Add(d, ts, attrs) => tl.add(d, ts, attrs),
+            Query::Edit(i, edit) => tl.edit(i, edit),
+            q => panic!("not an add or edit: {:?}", q),
+        };
+        run(&mut tl, "add \"landlord\"").unwrap();
+        run(&mut tl, "add \"get paid\"").unwrap();
+        let rent = run(
+            &mut tl,
+            "add \"rent\" !high due:2026-11-01 every:month-on-1 parent:0 after:1",
+        )
+        .unwrap();
+        let all = tl.get(rent).unwrap().attributes();
+
+        // What the edit does not mention stays as it was.
+        run(&mut tl, "edit 2 \"pay rent\"").unwrap();
+        assert_eq!(tl.get(rent).unwrap().attributes(), all);
+
+        // Each attribute can be cleared on its own.
+        for (line, kind) in &[
+            ("edit 2 \"pay rent\" !none", AttributeKind::Priority),
+            ("edit 2 \"pay rent\" due:none", AttributeKind::Due),
+            ("edit 2 \"pay rent\" every:none", AttributeKind::Recurrence),
+            ("edit 2 \"pay rent\" parent:none", AttributeKind::Parent),
+            ("edit 2 \"pay rent\" after:none", AttributeKind::After),
+        ] {
+            let mut expected = all.clone();
+            match kind {
+                AttributeKind::Priority => expected.priority = None,
+                AttributeKind::Due => expected.due = None,
+                AttributeKind::Recurrence => expected.recurrence = None,
+                AttributeKind::Parent => expected.parent = None,
+                AttributeKind::After => expected.after = vec![],
+            }
+            run(&mut tl, line).unwrap();
+            assert_eq!(tl.get(rent).unwrap().attributes(), expected, "{}", line);
+            tl.edit(rent, Edit::All(Description::new("pay rent"), vec![], all.clone()))
+                .unwrap();
+        }
+
+        // Given ones replace the item's.
+        run(&mut tl, "edit 2 \"pay rent\" !low due:2026-12-01 after:0").unwrap();
+        let item = tl.get(rent).unwrap();
+        assert_eq!(item.priority, Some(Priority::Low));
+        assert_eq!(item.due, Some("2026-12-01".parse().unwrap()));
+        assert_eq!(item.after, vec![Index::new(0)]);
+        assert_eq!(item.recurrence, Some(Recurrence::MonthlyOn(1)));
+    }
+
+    #[test]
+    fn test_hierarchical_tags() {
+        let mut tl = TodoList::new();
+        tl.push(Description::new("fix login"), Tag::from_strings(vec!["work/backend/auth"]));
+        tl.push(Description::new("fix api"), Tag::from_strings(vec!["work/backend"]));
+        tl.push(Description::new("fix css"), Tag::from_strings(vec!["work/frontend"]));
+        tl.push(Description::new("buy milk"), Tag::from_strings(vec!["home", "backend"]));
+
+        assert_eq!(found(&tl, "search #work/backend"), vec![1, 0]);
+        assert_eq!(found(&tl, "search --exact #work/backend"), vec![1, 0]);
+        assert_eq!(found(&tl, "search --exact #work"), vec![2, 1, 0]);
+        assert_eq!(found(&tl, "search #wrk/fr"), vec![2]);
+        assert_eq!(found(&tl, "search #backend"), vec![3]);
+        assert_eq!(found(&tl, "search --exact #work/backend/auth/x"), Vec::<u64>::new());
+
+        let r = &tl.search(parser_search("search login #w/ba"))[0];
+        assert_eq!(
+            r.render(Highlight::Brackets),
+            "0 \"fix [login]\" #[w]ork[/ba]ckend/auth"
+        );
+        let r = &tl.search(parser_search("search --rank #work/b"))[0];
+        assert_eq!(r.tag_matches, vec![vec![0, 1, 2, 3, 4, 5]]);
+
+        let tree = tl.tag_tree();
+        let names: Vec<(&str, usize, usize)> = tree
+            .iter()
+            .map(|n| (n.tag.value(), n.items, n.children.len()))
+            .collect();
+        assert_eq!(names, vec![("backend", 1, 0), ("home", 1, 0), ("work", 3, 2)]);
+        let work = &tree[2];
+        assert_eq!(work.children[0].tag, Tag::new("work/backend"));
+        assert_eq!(work.children[0].items, 2);
+        assert_eq!(work.children[0].children[0].tag.name(), "auth");
+
+        // Paths that differ only in case are one node, spelled as first seen.
+        tl.push(Description::new("fix build"), Tag::from_strings(vec!["Work/CI", "WORK"]));
+        let tree = tl.tag_tree();
+        assert_eq!(tree.len(), 3);
+        assert_eq!((tree[2].tag.value(), tree[2].items), ("work", 4));
+        let names: Vec<&str> = tree[2].children.iter().map(|n| n.tag.value()).collect();
+        assert_eq!(names, vec!["work/backend", "Work/CI", "work/frontend"]);
+    }
+
+    #[test]
+    fn test_tag_management() {
+        let mut tl = TodoList::new();
+        tl.push(Description::new("fix login"), Tag::from_strings(vec!["work/auth", "urgent"]));
+        tl.push(Description::new("fix api"), Tag::from_strings(vec!["work"]));
+        tl.push(Description::new("buy milk"), Tag::from_strings(vec!["shop", "errands"]));
+        tl.push(Description::new("post letter"), Tag::from_strings(vec!["errands"]));
+        tl.done_with_index(Index::new(3)).unwrap();
+
+        let summary: Vec<String> = tl.tag_summary().iter().map(|s| s.to_string()).collect();
+        assert_eq!(
+            summary,
+            vec![
+                "#errands: 1 open, 1 done",
+                "#shop: 1 open, 0 done",
+                "#urgent: 1 open, 0 done",
+                "#work: 1 open, 0 done",
+                "#work/auth: 1 open, 0 done",
+            ]
+        );
+
+        // Renaming a tag takes the tags below it along.
+        assert_eq!(tl.rename_tags(&[Tag::new("work")], &Tag::new("job")), Ok(2));
+        assert_eq!(
+            tl.get(Index::new(0)).unwrap().tags(),
+            Tag::from_strings(vec!["job/auth", "urgent"])
+        );
+        assert_eq!(
+            tl.rename_tags(&[Tag::new("wor")], &Tag::new("job")),
+            Err(QueryError::UnknownTag(Tag::new("wor")))
+        );
+
+        let merge = [Tag::new("shop"), Tag::new("errands")];
+        assert_eq!(tl.rename_tags(&merge, &Tag::new("chores")), Ok(2));
+        assert_eq!(
+            tl.get(Index::new(2)).unwrap().tags(),
+            Tag::from_strings(vec!["chores"])
+        );
+        let found = tl.search(parser_search("search --all #chores"));
+        assert_eq!(found.len(), 2);
+
+        let changes = [
+            TagChange::Add(Tag::new("home")),
+            TagChange::Remove(Tag::new("chores")),
+            TagChange::Add(Tag::new("home")),
+        ];
+        tl.retag(Index::new(2), &changes).unwrap();
+        assert_eq!(
+            tl.get(Index::new(2)).unwrap().tags(),
+            Tag::from_strings(vec!["home"])
+        );
+        assert_eq!(tl.search(parser_search("search #hom")).len(), 1);
+        assert_eq!(
+            tl.retag(Index::new(2), &[TagChange::Remove(Tag::new("chores"))]),
+            Err(QueryError::NotTagged(Index::new(2), Tag::new("chores")))
+        );
+
+        // Tags compare ignoring case, the way searches match them.
+        tl.push(Description::new("call bank"), Tag::from_strings(vec!["Chores/Bank"]));
+        tl.push(Description::new("pay bank"), Tag::from_strings(vec!["chores/bank", "URGENT"]));
+        let summary: Vec<String> = tl.tag_summary().iter().map(|s| s.to_string()).collect();
+        assert!(summary.contains(&String::from("#Chores/Bank: 2 open, 0 done")));
+        assert!(summary.contains(&String::from("#urgent: 2 open, 0 done")));
+        tl.delete(Index::new(5)).unwrap();
+        assert_eq!(tl.rename_tags(&[Tag::new("CHORES")], &Tag::new("chores")), Ok(2));
+        assert_eq!(
+            tl.get(Index::new(4)).unwrap().tags(),
+            Tag::from_strings(vec!["chores/Bank"])
+        );
+        tl.retag(Index::new(4), &[TagChange::Remove(Tag::new("chores/bank"))]).unwrap();
+        tl.retag(Index::new(4), &[TagChange::Add(Tag::new("Home"))]).unwrap();
+        tl.retag(Index::new(4), &[TagChange::Add(Tag::new("home"))]).unwrap();
+        assert_eq!(
+            tl.get(Index::new(4)).unwrap().tags(),
+            Tag::from_strings(vec!["Home"])
+        );
+    }
+
+    #[test]
+    fn test_recurrence_after() {
+        let date = |s: &str| -> Date { s.parse().unwrap() };
+        let after = |r: Recurrence, d: &str| r.after(date(d)).to_string();
+        assert_eq!(after(Recurrence::Days(2), "2026-10-30"), "2026-11-01");
+        assert_eq!(after(Recurrence::Weeks(2), "2026-10-15"), "2026-10-29");
+        assert_eq!(after(Recurrence::Monthly, "2026-01-31"), "2026-02-28");
+        assert_eq!(after(Recurrence::MonthlyOn(1), "2026-10-15"), "2026-11-01");
+        assert_eq!(after(Recurrence::MonthlyOn(1), "2026-10-01"), "2026-11-01");
+        assert_eq!(after(Recurrence::MonthlyOn(20), "2026-10-15"), "2026-10-20");
+        assert_eq!(after(Recurrence::MonthlyOn(31), "2026-01-31"), "2026-02-28");
+        assert_eq!(after(Recurrence::MonthlyOn(31), "2026-02-28"), "2026-03-31");
+    }
+
+    fn parser_search(line: &str) -> SearchParams {
+        match parser::parse_line(1, line).unwrap() {
+            Query::Search(sp) => sp,
+            q => panic!("not a search: {:?}", q),
+        }
+    }
+
+    fn found(tl: &TodoList, line: &str) -> Vec<u64> {
+        tl.search(parser_search(line))
+            .iter()
+            .map(|r| r.index.value())
+            .collect()
+    }
+
+    #[test]
+    fn test_delete_keeps_indices() {
+        let mut tl = TodoList::new();
+        let bread = tl.push(Description::new("buy bread"), vec![]);
+        let milk = tl.push(Description::new("buy milk"), vec![]);
+
+        assert_eq!(tl.delete(bread), Ok(bread));
+        assert_eq!(tl.delete(bread), Err(QueryError::Deleted(bread)));
+        assert_eq!(tl.done_with_index(bread), Err(QueryError::Deleted(bread)));
+        assert_eq!(
+            tl.delete(Index::new(2)),
+            Err(QueryError::InvalidIndex(Index::new(2)))
+        );
+
+        let found: Vec<Index> = tl
+            .search(parser_search("search buy"))
+            .iter()
+            .map(|r| r.index)
+            .collect();
+        assert_eq!(found, vec![milk]);
+        assert_eq!(
+            tl.push(Description::new("call parents"), vec![]),
+            Index::new(2)
+        );
+        assert_eq!(tl.done_with_index(milk), Ok(milk));
     }
 }
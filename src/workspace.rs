@@ -0,0 +1,266 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use crate::*;
+
+/// The list a new workspace starts out with.
+pub const DEFAULT_LIST: &str = "default";
+
+/// Named todo lists, each with its own indices, one of which is in use:
+/// queries other than `use`, `lists`, `move` and searches with
+/// `--all-lists` go to that one.
+///
+/// The clock and rules of the workspace are handed to every list in it.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    lists: BTreeMap<String, TodoList>,
+    current: String,
+    clock: Clock,
+    subtasks_block_done: bool,
+}
+
+/// Workspaces are equal when they hold the same lists and use the same one.
+impl PartialEq for Workspace {
+    fn eq(&self, other: &Workspace) -> bool {
+        self.current == other.current && self.lists == other.lists
+    }
+}
+
+impl Eq for Workspace {}
+
+impl Default for Workspace {
+    fn default() -> Workspace {
+        Workspace::new()
+    }
+}
+
+impl Workspace {
+    /// A workspace with just an empty `DEFAULT_LIST`.
+    pub fn new() -> Workspace {
+        Workspace::restore(
+            DEFAULT_LIST,
+            vec![(String::from(DEFAULT_LIST), TodoList::new())],
+        )
+    }
+
+    /// Rebuilds a workspace from its lists, e.g. when loading a snapshot.
+    /// The list in use is created if it is not among them.
+    pub fn restore(current: &str, lists: Vec<(String, TodoList)>) -> Workspace {
+        let mut ws = Workspace {
+            lists: lists.into_iter().collect(),
+            current: String::new(),
+            clock: Clock::System,
+            subtasks_block_done: true,
+        };
+        ws.use_list(current);
+        ws
+    }
+
+    pub fn clock(&self) -> Clock {
+        self.clock
+    }
+
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+        for tl in self.lists.values_mut() {
+            tl.set_clock(clock);
+        }
+    }
+
+    pub fn now(&self) -> Timestamp {
+        self.clock.now()
+    }
+
+    pub fn today(&self) -> Date {
+        self.clock.today()
+    }
+
+    pub fn subtasks_block_done(&self) -> bool {
+        self.subtasks_block_done
+    }
+
+    /// See `TodoList::set_subtasks_block_done`.
+    pub fn set_subtasks_block_done(&mut self, block: bool) {
+        self.subtasks_block_done = block;
+        for tl in self.lists.values_mut() {
+            tl.set_subtasks_block_done(block);
+        }
+    }
+
+    /// The name of the list in use.
+    pub fn current_name(&self) -> &str {
+        &self.current
+    }
+
+    pub fn current(&self) -> &TodoList {
+        &self.lists[&self.current]
+    }
+
+    pub fn current_mut(&mut self) -> &mut TodoList {
+        self.lists
+            .get_mut(&self.current)
+            .expect("the list in use exists")
+    }
+
+    /// The lists by name.
+    pub fn lists(&self) -> impl Iterator<Item = (&str, &TodoList)> {
+        self.lists.iter().map(|(name, tl)| (name.as_str(), tl))
+    }
+
+    pub fn get(&self, name: &str) -> Result<&TodoList, QueryError> {
+        self.lists
+            .get(name)
+            .ok_or_else(|| QueryError::UnknownList(name.to_owned()))
+    }
+
+    /// Switches to the list called `name`, creating it if there is none.
+    pub fn use_list(&mut self, name: &str) {
+        if !self.lists.contains_key(name) {
+            let mut tl = TodoList::new();
+            tl.set_clock(self.clock);
+            tl.set_subtasks_block_done(self.subtasks_block_done);
+            self.lists.insert(name.to_owned(), tl);
+        }
+        self.current = name.to_owned();
+    }
+
+    /// How many open and done items each list has.
+    pub fn summary(&self) -> Vec<ListSummary> {
+        self.lists()
+            .map(|(name, tl)| {
+                let done = tl.items().filter(|item| item.done).count();
+                ListSummary {
+                    name: name.to_owned(),
+                    open: tl.items().count() - done,
+                    done,
+                    current: name == self.current,
+                }
+            })
+            .collect()
+    }
+
+    /// Moves the item from the list in use to the end of the list called
+    /// `to`, where it gets a new index; see `TodoList::adopt`.
+    pub fn move_item(&mut self, idx: Index, to: &str) -> Result<Index, QueryError> {
+        self.get(to)?;
+        if to == self.current {
+            return self.current().get(idx).map(|_| idx);
+        }
+        let item = self.current_mut().take(idx)?;
+        let target = self.lists.get_mut(to).expect("checked above");
+        Ok(target.adopt(item))
+    }
+
+    /// Searches the list in use or, with `sp.all_lists`, every list. Results
+    /// from several lists are labelled with their list and come list by
+    /// list, unless ranked or ordered by priority.
+    pub fn search(&self, sp: SearchParams) -> Vec<SearchResult> {
+        if !sp.all_lists {
+            return self.current().search(sp);
+        }
+        let mut results: Vec<SearchResult> = vec![];
+        for (name, tl) in self.lists() {
+            results.extend(tl.search(sp.clone()).into_iter().map(|mut r| {
+                r.list = Some(name.to_owned());
+                r
+            }));
+        }
+        if sp.rank {
+            results.sort_by_key(|r| Reverse(r.score));
+        }
+        if sp.by_priority {
+            results.sort_by_key(|r| Reverse(r.attributes.priority));
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(ws: &Workspace, line: &str) -> Vec<String> {
+        match parser::parse_line(1, line) {
+            Ok(Query::Search(sp)) => ws.search(sp).iter().map(|r| r.to_string()).collect(),
+            other => panic!("not a search: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lists_have_their_own_indices() {
+        let mut ws = Workspace::new();
+        ws.current_mut().push(Description::new("buy milk"), vec![]);
+        ws.use_list("work");
+        assert_eq!(ws.current_name(), "work");
+        assert_eq!(
+            ws.current_mut().push(Description::new("fix build"), vec![]),
+            Index::new(0)
+        );
+        ws.current_mut()
+            .push(Description::new("buy a monitor"), vec![]);
+        ws.current_mut().done_with_index(Index::new(0)).unwrap();
+
+        assert_eq!(search(&ws, "search buy"), vec!["1 \"buy a monitor\""]);
+        assert_eq!(
+            search(&ws, "search --all-lists --all b"),
+            vec![
+                "default:0 \"buy milk\"",
+                "work:1 \"buy a monitor\"",
+                "work:0 (done) \"fix build\"",
+            ]
+        );
+        let summary: Vec<String> = ws.summary().iter().map(|s| s.to_string()).collect();
+        assert_eq!(
+            summary,
+            vec!["default: 1 open, 0 done", "work: 1 open, 1 done (in use)"]
+        );
+    }
+
+    #[test]
+    fn test_move_item() {
+        let mut ws = Workspace::new();
+        ws.use_list("home");
+        ws.use_list(DEFAULT_LIST);
+        let release = ws.current_mut().push(Description::new("release"), vec![]);
+        let parent = Attributes {
+            parent: Some(release),
+            ..Attributes::default()
+        };
+        let tests = ws
+            .current_mut()
+            .add(Description::new("write tests"), vec![], parent.clone())
+            .unwrap();
+        let milk = ws
+            .current_mut()
+            .add(Description::new("buy milk"), vec![], parent)
+            .unwrap();
+
+        assert_eq!(ws.move_item(milk, "home"), Ok(Index::new(0)));
+        assert_eq!(ws.current().get(milk), Err(QueryError::Deleted(milk)));
+        let moved = ws.get("home").unwrap().get(Index::new(0)).unwrap();
+        assert_eq!(
+            (moved.description(), moved.parent),
+            (Description::new("buy milk"), None)
+        );
+        assert_eq!(ws.move_item(tests, DEFAULT_LIST), Ok(tests));
+        assert_eq!(
+            ws.move_item(tests, "work"),
+            Err(QueryError::UnknownList(String::from("work")))
+        );
+        assert_eq!(
+            ws.move_item(Index::new(9), "home"),
+            Err(QueryError::InvalidIndex(Index::new(9)))
+        );
+    }
+
+    #[test]
+    fn test_new_lists_get_the_clock_and_rules() {
+        let mut ws = Workspace::new();
+        let at = Timestamp::from(Date::new(2026, 10, 15).unwrap());
+        ws.set_clock(Clock::Fixed(at));
+        ws.set_subtasks_block_done(false);
+        ws.use_list("work");
+        assert_eq!(ws.current().clock(), Clock::Fixed(at));
+        assert!(!ws.current().subtasks_block_done());
+    }
+}
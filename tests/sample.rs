@@ -0,0 +1,209 @@
+use std::fs;
+
+use assert_cmd::Command;
+
+#[test]
+fn sample_input_produces_documented_output() {
+    let input = fs::read_to_string("tests/fixtures/sample.in").unwrap();
+    let expected = fs::read_to_string("tests/fixtures/sample.out").unwrap();
+
+    let output = Command::cargo_bin("application")
+        .unwrap()
+        .write_stdin(input)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim_end(),
+        expected.trim_end()
+    );
+}
+
+#[test]
+fn journal_persists_list_between_runs() {
+    let path = std::env::temp_dir().join(format!("todo_swamp-cli-{}.journal", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    Command::cargo_bin("application")
+        .unwrap()
+        .arg("--stream")
+        .arg("--journal")
+        .arg(&path)
+        .write_stdin("add \"buy bread\" #groceries\nadd \"buy milk\" #groceries\ndone 0\n")
+        .assert()
+        .success()
+        .stdout("0\n1\ndone\n");
+
+    Command::cargo_bin("application")
+        .unwrap()
+        .arg("--stream")
+        .arg("--journal")
+        .arg(&path)
+        .write_stdin("add \"call parents\" #relatives\nsearch #groceries\n")
+        .assert()
+        .success()
+        .stdout("2\n1 item(s) found\n1 \"buy milk\" #groceries\n");
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn truncated_journal_entry_is_reported() {
+    let path = std::env::temp_dir().join(format!(
+        "todo_swamp-cli-truncated-{}.journal",
+        std::process::id()
+    ));
+    fs::write(&path, "add \"buy bread\"\nadd \"buy mi").unwrap();
+
+    Command::cargo_bin("application")
+        .unwrap()
+        .arg("--stream")
+        .arg("--journal")
+        .arg(&path)
+        .write_stdin("search buy\n")
+        .assert()
+        .success()
+        .stdout("1 item(s) found\n0 \"buy bread\"\n")
+        .stderr("Warning: dropping truncated journal entry \"add \\\"buy mi\"\n");
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn compact_keeps_list_and_empties_journal() {
+    let path = std::env::temp_dir().join(format!(
+        "todo_swamp-cli-compact-{}.journal",
+        std::process::id()
+    ));
+    let mut snapshot = path.clone().into_os_string();
+    snapshot.push(".snapshot");
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(&snapshot);
+
+    let run = |args: &[&str], stdin: &str| {
+        Command::cargo_bin("application")
+            .unwrap()
+            .arg("--stream")
+            .arg("--journal")
+            .arg(&path)
+            .args(args)
+            .write_stdin(stdin.to_owned())
+            .assert()
+            .success()
+    };
+
+    run(
+        &[],
+        "add \"buy bread\" #groceries\nadd \"buy milk\" #groceries\ndone 0\n",
+    );
+    run(&["--compact"], "").stdout("");
+    assert_eq!(fs::read_to_string(&path).unwrap(), "# generation 1\n");
+    run(&[], "add \"call parents\"\nsearch b\n")
+        .stdout("2\n1 item(s) found\n1 \"buy milk\" #groceries\n");
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&snapshot).unwrap();
+}
+
+#[test]
+fn parse_errors_are_reported_on_stderr() {
+    Command::cargo_bin("application")
+        .unwrap()
+        .arg("--stream")
+        .write_stdin("add \"buy bread\"\nad \"x\"\ndone abc\n")
+        .assert()
+        .success()
+        .stdout("0\n")
+        .stderr(
+            "Error: Could not parse the query at line 2, column 1: expected a command, found \"ad\".\n\
+             Error: Could not parse the query at line 3, column 6: expected an index, found \"abc\".\n",
+        );
+}
+
+#[test]
+fn strict_mode_fails_when_a_line_failed() {
+    Command::cargo_bin("application")
+        .unwrap()
+        .args(["--strict", "--stream"])
+        .write_stdin("add \"buy bread\"\ndone 5\n")
+        .assert()
+        .code(1)
+        .stdout("0\n");
+
+    Command::cargo_bin("application")
+        .unwrap()
+        .args(["--strict", "--stream"])
+        .write_stdin("add \"buy bread\"\n\ndone 0\n")
+        .assert()
+        .success();
+}
+
+#[test]
+fn sample_input_has_no_warnings() {
+    let input = fs::read_to_string("tests/fixtures/sample.in").unwrap();
+
+    Command::cargo_bin("application")
+        .unwrap()
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stderr("");
+}
+
+#[test]
+fn batch_count_mismatches_are_reported() {
+    Command::cargo_bin("application")
+        .unwrap()
+        .write_stdin("1\nadd \"buy bread\"\nadd \"buy milk\"\n")
+        .assert()
+        .success()
+        .stdout("0\n1\n")
+        .stderr("Warning: line 3: more queries than the 1 announced\n");
+
+    Command::cargo_bin("application")
+        .unwrap()
+        .write_stdin("3\nadd \"buy bread\"\n")
+        .assert()
+        .success()
+        .stdout("0\n")
+        .stderr("Warning: expected 3 queries, got 1\n");
+}
+
+#[test]
+fn batch_input_requires_a_query_count() {
+    Command::cargo_bin("application")
+        .unwrap()
+        .write_stdin("add \"buy bread\"\n")
+        .assert()
+        .code(1)
+        .stdout("");
+}
+
+#[test]
+fn highlight_marks_matches_with_brackets_when_piped() {
+    Command::cargo_bin("application")
+        .unwrap()
+        .args(["--stream", "--highlight"])
+        .write_stdin("add \"buy milk\" #groceries\nsearch ml #gro\n")
+        .assert()
+        .success()
+        .stdout("0\n1 item(s) found\n0 \"buy [m]i[l]k\" #[gro]ceries\n");
+}
+
+#[test]
+fn today_flag_fixes_the_date_for_due_searches() {
+    Command::cargo_bin("application")
+        .unwrap()
+        .args(["--stream", "--today", "2026-10-15"])
+        .write_stdin(
+            "add \"pay rent\" due:2026-10-01\nadd \"file taxes\" due:today\nsearch is:overdue\n\
+             search due:this-week\n",
+        )
+        .assert()
+        .success()
+        .stdout(
+            "0\n1\n1 item(s) found\n0 \"pay rent\" due:2026-10-01\n\
+             1 item(s) found\n1 \"file taxes\" due:2026-10-15\n",
+        );
+}